@@ -0,0 +1,487 @@
+use std::{collections::HashSet, fmt, fs, path::Path, sync::Mutex};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Deserializer, Serialize};
+use time::OffsetDateTime;
+use yahoo_finance_api::Quote;
+
+use crate::{
+    indicators,
+    monitor::Monitor,
+    notify::{Notification, Notifier, NotifyConfig, StdoutSink},
+    timeframe::Range,
+    volatility,
+};
+
+/// What has to happen to the latest bar of a stock for an alert to be emitted
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum Condition {
+    /// The close went from at or below the price to above it
+    CrossesAbove { price: f64 },
+    /// The close went from at or above the price to below it
+    CrossesBelow { price: f64 },
+    /// The bar's high to low range is above the given percentage of its close
+    DailyRangeAbove { percent: f64 },
+    RsiBelow {
+        level: f64,
+        #[serde(default = "default_rsi_period")]
+        period: usize,
+    },
+    RsiAbove {
+        level: f64,
+        #[serde(default = "default_rsi_period")]
+        period: usize,
+    },
+    /// The close is above every other close of the range
+    NewHigh {
+        #[serde(default = "default_range", deserialize_with = "deserialize_range")]
+        range: Range,
+    },
+    /// The close is below every other close of the range
+    NewLow {
+        #[serde(default = "default_range", deserialize_with = "deserialize_range")]
+        range: Range,
+    },
+}
+
+fn default_rsi_period() -> usize {
+    14
+}
+
+fn default_range() -> Range {
+    Range::SixMonths
+}
+
+fn deserialize_range<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Range, D::Error> {
+    let range = String::deserialize(deserializer)?;
+    Range::from_str(&range, true).map_err(serde::de::Error::custom)
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Condition::CrossesAbove { price } => write!(f, "close crosses above {}", price),
+            Condition::CrossesBelow { price } => write!(f, "close crosses below {}", price),
+            Condition::DailyRangeAbove { percent } => write!(f, "daily range above {}%", percent),
+            Condition::RsiBelow { level, period } => write!(f, "RSI({}) below {}", period, level),
+            Condition::RsiAbove { level, period } => write!(f, "RSI({}) above {}", period, level),
+            Condition::NewHigh { range } => write!(f, "new {} high", range_name(*range)),
+            Condition::NewLow { range } => write!(f, "new {} low", range_name(*range)),
+        }
+    }
+}
+
+fn range_name(range: Range) -> String {
+    range
+        .to_possible_value()
+        .map(|value| value.get_name().to_owned())
+        .unwrap_or_default()
+}
+
+impl Condition {
+    /// Checks the latest of the quotes, returning what happened when the condition is met
+    fn evaluate(&self, quotes: &[Quote]) -> Option<String> {
+        let latest = quotes.last()?;
+        let previous = quotes.len().checked_sub(2).map(|i| &quotes[i]);
+        match *self {
+            Condition::CrossesAbove { price } => (previous?.close <= price && latest.close > price)
+                .then(|| format!("closed at {:.2}, above {:.2}", latest.close, price)),
+            Condition::CrossesBelow { price } => (previous?.close >= price && latest.close < price)
+                .then(|| format!("closed at {:.2}, below {:.2}", latest.close, price)),
+            Condition::DailyRangeAbove { percent } => {
+                let range = volatility::daily_range(latest) * 100.0;
+                (range > percent).then(|| format!("ranged {:.2}% of its close", range))
+            }
+            Condition::RsiBelow { level, period } => {
+                let rsi = (*indicators::rsi(quotes, period).last()?)?;
+                (rsi < level).then(|| format!("RSI({}) is {:.2}", period, rsi))
+            }
+            Condition::RsiAbove { level, period } => {
+                let rsi = (*indicators::rsi(quotes, period).last()?)?;
+                (rsi > level).then(|| format!("RSI({}) is {:.2}", period, rsi))
+            }
+            Condition::NewHigh { range } | Condition::NewLow { range } => {
+                let start = range
+                    .start(OffsetDateTime::from_unix_timestamp(latest.timestamp as i64).ok()?)
+                    .unix_timestamp();
+                let mut closes = quotes[..quotes.len() - 1]
+                    .iter()
+                    .filter(|quote| quote.timestamp as i64 >= start)
+                    .map(|quote| quote.close)
+                    .peekable();
+                closes.peek()?;
+                if matches!(self, Condition::NewHigh { .. }) {
+                    let high = closes.fold(f64::NEG_INFINITY, f64::max);
+                    (latest.close > high).then(|| {
+                        format!(
+                            "closed at {:.2}, above the previous high of {:.2}",
+                            latest.close, high
+                        )
+                    })
+                } else {
+                    let low = closes.fold(f64::INFINITY, f64::min);
+                    (latest.close < low).then(|| {
+                        format!(
+                            "closed at {:.2}, below the previous low of {:.2}",
+                            latest.close, low
+                        )
+                    })
+                }
+            }
+        }
+    }
+
+    /// Start of the quotes needed to evaluate the condition
+    fn lookback(&self, today: OffsetDateTime) -> OffsetDateTime {
+        match *self {
+            Condition::NewHigh { range } | Condition::NewLow { range } => range.start(today),
+            // Leaves room for the RSI to settle
+            _ => Range::OneYear.start(today),
+        }
+    }
+}
+
+/// A condition watched on a stock
+#[derive(Clone, Debug, Deserialize)]
+pub struct Rule {
+    pub ticker: String,
+    #[serde(flatten)]
+    pub condition: Condition,
+}
+
+/// The rules and where to send their alerts, as read from the alerts file
+#[derive(Debug, Deserialize)]
+pub struct AlertConfig {
+    pub rules: Vec<Rule>,
+    #[serde(flatten)]
+    pub sinks: NotifyConfig,
+}
+
+/// A rule that was met, as sent to the notification sinks
+#[derive(Serialize)]
+pub struct Alert {
+    pub ticker: String,
+    pub rule: String,
+    pub time: String,
+    pub close: f64,
+    pub message: String,
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[{}] {} {}: {}",
+            self.time, self.ticker, self.rule, self.message
+        )
+    }
+}
+
+/// Evaluates the alert rules against the latest quotes
+pub struct Alerts {
+    rules: Vec<Rule>,
+    notifier: Notifier,
+    /// Rules already met on a bar, by rule index and bar timestamp, so that each alert is
+    /// only emitted once
+    emitted: Mutex<HashSet<(usize, u64)>>,
+}
+
+impl Alerts {
+    /// Reads the rules from a JSON file
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        Alerts::new(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn new(config: AlertConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Alerts {
+            notifier: Notifier::new(&config.sinks)?.with_sink(StdoutSink),
+            rules: config.rules,
+            emitted: Mutex::new(HashSet::new()),
+        })
+    }
+
+    /// Fetches the quotes of every stock with rules and emits the alerts of the rules met
+    /// by their latest bar
+    pub fn check(&self, monitor: &Monitor) {
+        let mut stock_names: Vec<&String> = self.rules.iter().map(|rule| &rule.ticker).collect();
+        stock_names.sort();
+        stock_names.dedup();
+
+        for stock_name in stock_names {
+            let rules: Vec<(usize, &Rule)> = self
+                .rules
+                .iter()
+                .enumerate()
+                .filter(|(_, rule)| &rule.ticker == stock_name)
+                .collect();
+            let today = monitor.today(std::slice::from_ref(stock_name));
+            let start_date = rules
+                .iter()
+                .map(|(_, rule)| rule.condition.lookback(today))
+                .min()
+                .unwrap_or(today);
+            let quotes = match monitor.provider.get_quote_history(
+                stock_name,
+                start_date,
+                today,
+                monitor.interval,
+            ) {
+                Ok(quotes) => quotes,
+                Err(error) => {
+                    eprintln!("Could not check the alerts of {}: {}", stock_name, error);
+                    continue;
+                }
+            };
+            let Some(latest) = quotes.last() else {
+                continue;
+            };
+
+            for (index, rule) in rules {
+                let Some(message) = rule.condition.evaluate(&quotes) else {
+                    continue;
+                };
+                if !self
+                    .emitted
+                    .lock()
+                    .unwrap()
+                    .insert((index, latest.timestamp))
+                {
+                    continue;
+                }
+                let alert = Alert {
+                    ticker: stock_name.clone(),
+                    rule: rule.condition.to_string(),
+                    time: monitor
+                        .interval
+                        .bar_time(latest.timestamp as i64)
+                        .format(monitor.interval.time_format())
+                        .to_string(),
+                    close: latest.close,
+                    message,
+                };
+                self.notifier.notify(&Notification::Alert(alert));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        notify::Sink,
+        provider::{ProviderError, QuoteProvider},
+        timeframe::Interval,
+        volatility::{Volatility, VolatilityMeasure},
+    };
+
+    /// Midnight UTC of 2024-03-01
+    const FIRST_DAY: u64 = 1_709_251_200;
+
+    fn quote(day: u64, close: f64) -> Quote {
+        Quote {
+            timestamp: FIRST_DAY + day * 86_400,
+            open: close,
+            high: close,
+            low: close,
+            volume: 1000,
+            close,
+            adjclose: close,
+        }
+    }
+
+    /// Daily quotes with the given closes
+    fn quotes(closes: &[f64]) -> Vec<Quote> {
+        closes
+            .iter()
+            .enumerate()
+            .map(|(day, close)| quote(day as u64, *close))
+            .collect()
+    }
+
+    fn message(condition: Condition, closes: &[f64]) -> Option<String> {
+        condition.evaluate(&quotes(closes))
+    }
+
+    #[test]
+    fn crossings_need_the_previous_close_on_the_other_side() {
+        let above = |closes: &[f64]| message(Condition::CrossesAbove { price: 102.0 }, closes);
+        assert_eq!(
+            above(&[101.0, 102.5]).as_deref(),
+            Some("closed at 102.50, above 102.00")
+        );
+        assert!(above(&[102.0, 103.0]).is_some());
+        assert!(above(&[101.0, 102.0]).is_none());
+        assert!(above(&[103.0, 104.0]).is_none());
+        assert!(above(&[103.0]).is_none());
+
+        let below = |closes: &[f64]| message(Condition::CrossesBelow { price: 102.0 }, closes);
+        assert_eq!(
+            below(&[103.0, 101.5]).as_deref(),
+            Some("closed at 101.50, below 102.00")
+        );
+        assert!(below(&[102.0, 101.0]).is_some());
+        assert!(below(&[103.0, 102.0]).is_none());
+        assert!(below(&[101.0, 100.0]).is_none());
+    }
+
+    #[test]
+    fn rsi_is_compared_to_its_level() {
+        let rising = [1.0, 2.0, 3.0, 4.0];
+        let falling = [4.0, 3.0, 2.0, 1.0];
+        let above = Condition::RsiAbove {
+            level: 70.0,
+            period: 2,
+        };
+        let below = Condition::RsiBelow {
+            level: 30.0,
+            period: 2,
+        };
+        assert_eq!(
+            message(above.clone(), &rising).as_deref(),
+            Some("RSI(2) is 100.00")
+        );
+        assert!(message(below.clone(), &rising).is_none());
+        assert_eq!(
+            message(below.clone(), &falling).as_deref(),
+            Some("RSI(2) is 0.00")
+        );
+        assert!(message(above, &falling).is_none());
+        // Not enough closes for the period
+        assert!(message(below, &[2.0, 1.0]).is_none());
+
+        // Gains of 1 and 1 then a loss of 1 average to an RSI of exactly 50
+        let at_50 = [10.0, 11.0, 12.0, 11.0];
+        let below = |level| Condition::RsiBelow { level, period: 2 };
+        assert!(message(below(50.0), &at_50).is_none());
+        assert!(message(below(50.1), &at_50).is_some());
+    }
+
+    #[test]
+    fn new_highs_and_lows_only_look_at_the_range() {
+        // A high and a low 60 days before the latest close, then 30 days of closes at 100
+        let mut quotes = vec![quote(0, 200.0), quote(1, 10.0)];
+        quotes.extend((31..61).map(|day| quote(day, 100.0)));
+        let with_latest = |close| {
+            let mut quotes = quotes.clone();
+            quotes.push(quote(61, close));
+            quotes
+        };
+
+        let high = |range| Condition::NewHigh { range };
+        assert_eq!(
+            high(Range::OneMonth)
+                .evaluate(&with_latest(150.0))
+                .as_deref(),
+            Some("closed at 150.00, above the previous high of 100.00")
+        );
+        assert!(high(Range::Max).evaluate(&with_latest(150.0)).is_none());
+        assert!(high(Range::OneMonth)
+            .evaluate(&with_latest(100.0))
+            .is_none());
+
+        let low = |range| Condition::NewLow { range };
+        assert_eq!(
+            low(Range::OneMonth).evaluate(&with_latest(50.0)).as_deref(),
+            Some("closed at 50.00, below the previous low of 100.00")
+        );
+        assert!(low(Range::Max).evaluate(&with_latest(50.0)).is_none());
+
+        // No earlier close in the range to compare with
+        assert!(high(Range::OneMonth)
+            .evaluate(&[quote(0, 100.0), quote(60, 150.0)])
+            .is_none());
+    }
+
+    /// Serves the quotes of a single stock, which the test can add bars to
+    struct FixtureProvider(Arc<Mutex<Vec<Quote>>>);
+
+    impl QuoteProvider for FixtureProvider {
+        fn get_quote_history(
+            &self,
+            _stock_name: &str,
+            start_date: OffsetDateTime,
+            end_date: OffsetDateTime,
+            _interval: Interval,
+        ) -> Result<Vec<Quote>, ProviderError> {
+            let (start, end) = (start_date.unix_timestamp(), end_date.unix_timestamp());
+            Ok(self
+                .0
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|quote| (start..=end).contains(&(quote.timestamp as i64)))
+                .cloned()
+                .collect())
+        }
+
+        fn get_latest_quote(&self, _stock_name: &str) -> Result<Quote, ProviderError> {
+            self.0
+                .lock()
+                .unwrap()
+                .last()
+                .cloned()
+                .ok_or_else(|| "No quotes".into())
+        }
+    }
+
+    /// Keeps the subjects of the notifications it was sent
+    struct RecordingSink(Arc<Mutex<Vec<String>>>);
+
+    impl Sink for RecordingSink {
+        fn send(&self, notification: &Notification) -> Result<(), Box<dyn std::error::Error>> {
+            self.0.lock().unwrap().push(notification.subject());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn rules_are_notified_once_per_bar() {
+        let quotes = Arc::new(Mutex::new(quotes(&[100.0, 101.0, 99.0, 105.0])));
+        let monitor = Monitor {
+            provider: Box::new(FixtureProvider(quotes.clone())),
+            interval: Interval::OneDay,
+            volatility: Volatility::new(VolatilityMeasure::Range, None, 20),
+            indicators: Vec::new(),
+            anchor_to_data: true,
+        };
+        let rule = |condition| Rule {
+            ticker: "TEST".to_owned(),
+            condition,
+        };
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let alerts = Alerts {
+            rules: vec![
+                rule(Condition::CrossesAbove { price: 102.0 }),
+                rule(Condition::CrossesBelow { price: 50.0 }),
+                rule(Condition::NewHigh {
+                    range: Range::SixMonths,
+                }),
+            ],
+            notifier: Notifier::new(&NotifyConfig::default())
+                .unwrap()
+                .with_sink(RecordingSink(sent.clone())),
+            emitted: Mutex::new(HashSet::new()),
+        };
+
+        alerts.check(&monitor);
+        alerts.check(&monitor);
+        assert_eq!(
+            *sent.lock().unwrap(),
+            [
+                "TEST alert: close crosses above 102",
+                "TEST alert: new 6mo high"
+            ]
+        );
+
+        // A higher close on the next bar is a new high again, but no longer a crossing
+        quotes.lock().unwrap().push(quote(4, 106.0));
+        alerts.check(&monitor);
+        alerts.check(&monitor);
+        assert_eq!(sent.lock().unwrap().len(), 3);
+        assert_eq!(sent.lock().unwrap()[2], "TEST alert: new 6mo high");
+    }
+}
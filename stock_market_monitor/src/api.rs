@@ -0,0 +1,352 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+};
+
+use chrono::{NaiveDate, NaiveDateTime};
+use clap::ValueEnum;
+use serde::Serialize;
+use time::OffsetDateTime;
+use yahoo_finance_api::Quote;
+
+use crate::{
+    monitor::Monitor,
+    report::StockReport,
+    server::Dashboard,
+    statistics::Statistics,
+    timeframe::{date_to_time, Interval, Range, Window},
+};
+
+/// An error returned to the client with its HTTP status code
+pub struct ApiError {
+    pub status: u16,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(status: u16, message: impl Into<String>) -> Self {
+        ApiError {
+            status,
+            message: message.into(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::json!({ "error": self.message }).to_string()
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+#[derive(Serialize)]
+struct Bar {
+    time: String,
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+    volume: u64,
+    adjclose: f64,
+    volatile: bool,
+}
+
+#[derive(Serialize)]
+struct PricePoint {
+    time: String,
+    price: f64,
+}
+
+#[derive(Serialize)]
+struct QuotesResponse {
+    ticker: String,
+    interval: &'static str,
+    start: String,
+    end: String,
+    quotes: Vec<Bar>,
+}
+
+#[derive(Serialize)]
+struct StatsResponse {
+    ticker: String,
+    interval: &'static str,
+    start: String,
+    end: String,
+    bars: usize,
+    last_close: PricePoint,
+    max_close: PricePoint,
+    min_close: PricePoint,
+    volatile_bars: usize,
+    volatile_percent: f64,
+    indicators: BTreeMap<String, String>,
+}
+
+#[derive(Serialize)]
+struct StatisticsResponse {
+    interval: &'static str,
+    start: String,
+    end: String,
+    #[serde(flatten)]
+    statistics: Statistics,
+}
+
+#[derive(Serialize)]
+struct VolatileResponse {
+    ticker: String,
+    interval: &'static str,
+    start: String,
+    end: String,
+    measure: String,
+    threshold: f64,
+    volatile_days: Vec<Bar>,
+}
+
+/// A report fetched for a request, with the window and interval it covers
+pub struct Requested {
+    pub report: StockReport,
+    pub interval: Interval,
+    pub window: Window,
+    pub dates: (OffsetDateTime, OffsetDateTime),
+}
+
+/// Answers `/api/{quotes,stats,statistics,volatile}/{ticker}`, optionally narrowed down with the
+/// `start`, `end`, `range` and `interval` query parameters
+pub fn handle(endpoint: &str, query: &str, dashboard: &Dashboard) -> Result<String, ApiError> {
+    let (resource, ticker) = endpoint
+        .split_once('/')
+        .ok_or_else(|| ApiError::new(404, "Not Found"))?;
+    if !["quotes", "stats", "statistics", "volatile"].contains(&resource) {
+        return Err(ApiError::new(404, "Not Found"));
+    }
+
+    let (window, interval) = parse_window(&parse_params(query), dashboard)?;
+    let requested = fetch_report(&decode(ticker), window, interval, dashboard)?;
+    respond(resource, &requested, &dashboard.monitor)
+}
+
+/// Serializes the quotes, stats, risk and return statistics or volatile days of a fetched
+/// report
+pub fn respond(
+    resource: &str,
+    requested: &Requested,
+    monitor: &Monitor,
+) -> Result<String, ApiError> {
+    let Requested {
+        report,
+        interval,
+        dates: (start_date, end_date),
+        ..
+    } = requested;
+    let interval = *interval;
+
+    let time_format = interval.time_format();
+    let format_time = |timestamp: i64| interval.bar_time(timestamp).format(time_format).to_string();
+    let (start, end) = (
+        format_time(start_date.unix_timestamp()),
+        format_time(end_date.unix_timestamp()),
+    );
+    let volatile_times: HashSet<NaiveDateTime> =
+        report.volatile_days.iter().map(|(time, _)| *time).collect();
+    let bar = |time: &NaiveDateTime, quote: &Quote| Bar {
+        time: time.format(time_format).to_string(),
+        open: quote.open,
+        high: quote.high,
+        low: quote.low,
+        close: quote.close,
+        volume: quote.volume,
+        adjclose: quote.adjclose,
+        volatile: volatile_times.contains(time),
+    };
+    let price_point = |(time, price): (NaiveDateTime, f64)| PricePoint {
+        time: time.format(time_format).to_string(),
+        price,
+    };
+
+    let json = match resource {
+        "quotes" => serde_json::to_string(&QuotesResponse {
+            ticker: report.stock_name.clone(),
+            interval: interval.as_str(),
+            start,
+            end,
+            quotes: report.quotes.iter().map(|(t, q)| bar(t, q)).collect(),
+        }),
+        "stats" => {
+            let quotes = report.raw_quotes();
+            serde_json::to_string(&StatsResponse {
+                ticker: report.stock_name.clone(),
+                interval: interval.as_str(),
+                start,
+                end,
+                bars: report.quotes.len(),
+                last_close: price_point(report.series[report.series.len() - 1]),
+                max_close: price_point(report.max_close),
+                min_close: price_point(report.min_close),
+                volatile_bars: report.volatile_days.len(),
+                volatile_percent: volatile_percent(report),
+                indicators: monitor
+                    .indicators
+                    .iter()
+                    .map(|indicator| (indicator.to_string(), indicator.latest(&quotes)))
+                    .collect(),
+            })
+        }
+        "statistics" => serde_json::to_string(&StatisticsResponse {
+            interval: interval.as_str(),
+            start,
+            end,
+            statistics: Statistics::new(report).ok_or_else(|| {
+                ApiError::new(422, "At least 3 bars are needed to compute the statistics")
+            })?,
+        }),
+        _ => serde_json::to_string(&VolatileResponse {
+            ticker: report.stock_name.clone(),
+            interval: interval.as_str(),
+            start,
+            end,
+            measure: monitor
+                .volatility
+                .measure
+                .to_possible_value()
+                .map(|v| v.get_name().to_owned())
+                .unwrap_or_default(),
+            threshold: monitor.volatility.threshold,
+            volatile_days: report
+                .volatile_days
+                .iter()
+                .map(|(t, q)| bar(t, q))
+                .collect(),
+        }),
+    };
+    json.map_err(|error| ApiError::new(500, error.to_string()))
+}
+
+/// Fetches the report of a stock for the window and interval
+pub fn fetch_report(
+    ticker: &str,
+    window: Window,
+    interval: Interval,
+    dashboard: &Dashboard,
+) -> Result<Requested, ApiError> {
+    if !is_valid_ticker(ticker) {
+        return Err(ApiError::new(
+            400,
+            format!("Invalid stock symbol {}", ticker),
+        ));
+    }
+    let monitor = &dashboard.monitor;
+    if !monitor.provider.is_valid_stock(ticker) {
+        return Err(ApiError::new(
+            404,
+            format!("The stock symbol {} is not valid", ticker),
+        ));
+    }
+    let (start_date, end_date) = window
+        .resolve(monitor.today(&[ticker.to_owned()]))
+        .map_err(|error| ApiError::new(400, error))?;
+    if start_date >= end_date {
+        return Err(ApiError::new(
+            400,
+            "The start date must be before the end date",
+        ));
+    }
+    let report = monitor
+        .report(ticker, start_date, end_date, interval)
+        .map_err(|error| ApiError::new(502, error.to_string()))?;
+    Ok(Requested {
+        report,
+        interval,
+        window,
+        dates: (start_date, end_date),
+    })
+}
+
+fn volatile_percent(report: &StockReport) -> f64 {
+    100.0 * report.volatile_days.len() as f64 / report.quotes.len() as f64
+}
+
+/// Stock symbols are also used as file names by the cache and local data files,
+/// so only the characters found in real symbols are accepted, ex. BRK-B, ^GSPC, EURUSD=X
+fn is_valid_ticker(ticker: &str) -> bool {
+    !ticker.is_empty()
+        && ticker.len() <= 20
+        && ticker
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || ".-^=".contains(c))
+        && !ticker.contains("..")
+}
+
+/// Splits a query string into its decoded parameters
+pub fn parse_params(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(name, value)| (decode(name), decode(value)))
+        .collect()
+}
+
+/// Decodes the percent-encoded characters of a URL component, ex. %5EGSPC to ^GSPC
+pub fn decode(component: &str) -> String {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (b'+', _) => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Reads the window and interval from the query, falling back to the command line options
+pub fn parse_window(
+    params: &HashMap<String, String>,
+    dashboard: &Dashboard,
+) -> Result<(Window, Interval), ApiError> {
+    let mut window = dashboard.window;
+    let mut interval = dashboard.monitor.interval;
+
+    let parse_date = |name: &str, value: &str| {
+        let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| {
+            ApiError::new(
+                400,
+                format!("Invalid {} date {}, expected YYYY-MM-DD", name, value),
+            )
+        })?;
+        date_to_time(date, false).map_err(|error| ApiError::new(400, error))?;
+        Ok(date)
+    };
+    if let Some(range) = params.get("range") {
+        window.range = Range::from_str(range, true)
+            .map_err(|_| ApiError::new(400, format!("Invalid range {}", range)))?;
+        window.start = None;
+    }
+    if let Some(start) = params.get("start") {
+        window.start = Some(parse_date("start", start)?);
+    }
+    if let Some(end) = params.get("end") {
+        window.end = Some(parse_date("end", end)?);
+    }
+    if let Some(value) = params.get("interval") {
+        interval = Interval::from_str(value, true)
+            .map_err(|_| ApiError::new(400, format!("Invalid interval {}", value)))?;
+    }
+    Ok((window, interval))
+}
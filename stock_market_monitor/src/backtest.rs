@@ -0,0 +1,440 @@
+use std::{fmt, path::Path, str::FromStr};
+
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+use crate::{
+    indicators, report::StockReport, statistics, timeframe::Interval, volatility::Volatility,
+};
+
+/// When to buy and sell
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Strategy {
+    /// Hold the stock while the fast simple moving average is above the slow one
+    SmaCrossover { fast: usize, slow: usize },
+    /// Buy after a volatile bar that closed below its open and sell a number of bars later
+    VolatileDip { hold: usize },
+}
+
+impl Strategy {
+    /// Whether to hold the stock after each bar, decided on that bar's close
+    fn positions(&self, report: &StockReport, volatility: &Volatility) -> Vec<bool> {
+        let quotes = report.raw_quotes();
+        match *self {
+            Strategy::SmaCrossover { fast, slow } => {
+                let fast = indicators::sma(&quotes, fast);
+                let slow = indicators::sma(&quotes, slow);
+                fast.iter()
+                    .zip(&slow)
+                    .map(|pair| matches!(pair, (Some(fast), Some(slow)) if fast > slow))
+                    .collect()
+            }
+            Strategy::VolatileDip { hold } => {
+                let flags = volatility.flags(&quotes);
+                let mut held_until = 0;
+                let mut positions = Vec::with_capacity(quotes.len());
+                for (i, quote) in quotes.iter().enumerate() {
+                    if i >= held_until && flags[i] && quote.close < quote.open {
+                        held_until = i + hold;
+                    }
+                    positions.push(i < held_until);
+                }
+                positions
+            }
+        }
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Strategy::SmaCrossover { fast, slow } => write!(f, "SMA crossover({}, {})", fast, slow),
+            Strategy::VolatileDip { hold } => write!(f, "Volatile dip({})", hold),
+        }
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let name = parts.next().unwrap_or_default().to_lowercase();
+        let params: Vec<usize> = parts
+            .map(|p| match p.parse::<usize>() {
+                Ok(p) if p > 0 => Ok(p),
+                _ => Err(format!("Invalid parameter {} in {}", p, s)),
+            })
+            .collect::<Result<_, _>>()?;
+        match name.as_str() {
+            "sma-crossover" => {
+                let (fast, slow) = (
+                    params.first().copied().unwrap_or(20),
+                    params.get(1).copied().unwrap_or(50),
+                );
+                if fast >= slow {
+                    return Err(format!(
+                        "The fast period of {} must be below the slow one",
+                        s
+                    ));
+                }
+                Ok(Strategy::SmaCrossover { fast, slow })
+            }
+            "volatile-dip" => Ok(Strategy::VolatileDip {
+                hold: params.first().copied().unwrap_or(5),
+            }),
+            _ => Err(format!(
+                "Unknown strategy {}, expected sma-crossover or volatile-dip",
+                name
+            )),
+        }
+    }
+}
+
+/// Strategy and trading costs of a backtest
+#[derive(Clone, Copy, Debug)]
+pub struct Backtest {
+    pub strategy: Strategy,
+    pub capital: f64,
+    /// Fee paid on every buy and sell
+    pub commission: f64,
+    /// Percentage of the price lost on every fill
+    pub slippage: f64,
+}
+
+/// A round trip, bought and sold at the open of the bar after the signal
+#[derive(Serialize)]
+pub struct Trade {
+    pub ticker: String,
+    pub entry_time: String,
+    pub entry_price: f64,
+    pub exit_time: String,
+    pub exit_price: f64,
+    pub shares: f64,
+    /// Profit after commissions
+    pub profit: f64,
+    pub return_percent: f64,
+}
+
+/// Trades, equity curve and metrics of a backtest over one stock
+pub struct BacktestResult {
+    pub stock_name: String,
+    pub interval: Interval,
+    pub strategy: Strategy,
+    pub trades: Vec<Trade>,
+    /// Equity of the strategy and of buying and holding the stock at every close
+    pub equity: Vec<(NaiveDateTime, f64, f64)>,
+    pub total_return: f64,
+    pub cagr: f64,
+    pub max_drawdown: f64,
+    pub sharpe: f64,
+    pub win_rate: f64,
+}
+
+impl Backtest {
+    /// Simulates the strategy over the report's quotes, buying with the whole equity.
+    /// A position still open after the last bar is sold at its close.
+    pub fn run(&self, report: &StockReport, volatility: &Volatility) -> BacktestResult {
+        let positions = self.strategy.positions(report, volatility);
+        let time_format = report.interval.time_format();
+        let slippage = self.slippage / 100.0;
+        let quotes = &report.quotes;
+        let first_close = quotes[0].1.close;
+
+        let mut cash = self.capital;
+        let mut shares = 0.0;
+        let mut entry: Option<(NaiveDateTime, f64, f64)> = None;
+        let mut trades = Vec::new();
+        let mut equity = Vec::with_capacity(quotes.len());
+
+        for (i, (time, quote)) in quotes.iter().enumerate() {
+            let last = i == quotes.len() - 1;
+            // Fill the signal of the previous bar at this bar's open
+            let wanted = i > 0 && positions[i - 1];
+            if wanted && entry.is_none() && cash > self.commission {
+                let price = quote.open * (1.0 + slippage);
+                shares = (cash - self.commission) / price;
+                entry = Some((*time, price, cash));
+                cash = 0.0;
+            }
+            let exit = match entry {
+                Some(_) if !wanted => Some(quote.open * (1.0 - slippage)),
+                Some(_) if last => Some(quote.close * (1.0 - slippage)),
+                _ => None,
+            };
+            if let (Some(price), Some((entry_time, entry_price, invested))) = (exit, entry) {
+                cash = shares * price - self.commission;
+                trades.push(Trade {
+                    ticker: report.stock_name.clone(),
+                    entry_time: entry_time.format(time_format).to_string(),
+                    entry_price,
+                    exit_time: time.format(time_format).to_string(),
+                    exit_price: price,
+                    shares,
+                    profit: cash - invested,
+                    return_percent: (cash / invested - 1.0) * 100.0,
+                });
+                shares = 0.0;
+                entry = None;
+            }
+            equity.push((
+                *time,
+                cash + shares * quote.close,
+                self.capital * quote.close / first_close,
+            ));
+        }
+
+        // Metrics of the equity curve
+        let final_equity = equity[equity.len() - 1].1;
+        let years = (equity[equity.len() - 1].0 - equity[0].0).num_days() as f64 / 365.25;
+        let values: Vec<f64> = equity.iter().map(|(_, value, _)| *value).collect();
+        let returns = statistics::returns(&values);
+        let wins = trades.iter().filter(|trade| trade.profit > 0.0).count();
+
+        BacktestResult {
+            stock_name: report.stock_name.clone(),
+            interval: report.interval,
+            strategy: self.strategy,
+            total_return: (final_equity / self.capital - 1.0) * 100.0,
+            cagr: if years > 0.0 {
+                ((final_equity / self.capital).powf(1.0 / years) - 1.0) * 100.0
+            } else {
+                0.0
+            },
+            max_drawdown: statistics::max_drawdown(&values).depth * 100.0,
+            sharpe: statistics::sharpe_ratio(&returns, report.interval.bars_per_year()),
+            win_rate: if trades.is_empty() {
+                0.0
+            } else {
+                100.0 * wins as f64 / trades.len() as f64
+            },
+            trades,
+            equity,
+        }
+    }
+}
+
+impl BacktestResult {
+    pub fn print(&self) {
+        let (_, final_equity, buy_and_hold) = self.equity[self.equity.len() - 1];
+        println!(
+            "{} Backtest of {}:\nTrades: {}\nWin Rate: {:.1}%\nFinal Equity: ${:.2} (buy and hold ${:.2})\nTotal Return: {:.2}%\nCAGR: {:.2}%\nMax Drawdown: {:.2}%\nSharpe Ratio: {:.2}",
+            self.stock_name,
+            self.strategy,
+            self.trades.len(),
+            self.win_rate,
+            final_equity,
+            buy_and_hold,
+            self.total_return,
+            self.cagr,
+            self.max_drawdown,
+            self.sharpe
+        );
+    }
+}
+
+/// Writes the trades of every backtest to one CSV file
+pub fn write_trades(
+    path: &Path,
+    results: &[BacktestResult],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut writer = csv::Writer::from_path(path)?;
+    if results.iter().all(|result| result.trades.is_empty()) {
+        // The header is otherwise written along with the first trade
+        writer.write_record([
+            "ticker",
+            "entry_time",
+            "entry_price",
+            "exit_time",
+            "exit_price",
+            "shares",
+            "profit",
+            "return_percent",
+        ])?;
+    }
+    for trade in results.iter().flat_map(|result| &result.trades) {
+        writer.serialize(trade)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use yahoo_finance_api::Quote;
+
+    use super::*;
+    use crate::volatility::VolatilityMeasure;
+
+    fn assert_close(value: f64, expected: f64) {
+        assert!((value - expected).abs() < 1e-9, "{} != {}", value, expected);
+    }
+
+    /// Daily bars from 2024-03-01 with the given opens and closes
+    fn report(bars: &[(f64, f64)]) -> StockReport {
+        let quotes: Vec<Quote> = bars
+            .iter()
+            .enumerate()
+            .map(|(day, &(open, close))| Quote {
+                timestamp: 1_709_251_200 + day as u64 * 86_400,
+                open,
+                high: open.max(close),
+                low: open.min(close),
+                volume: 1000,
+                close,
+                adjclose: close,
+            })
+            .collect();
+        StockReport::new("TEST", &quotes, Interval::OneDay, &volatility()).unwrap()
+    }
+
+    fn volatility() -> Volatility {
+        Volatility::new(VolatilityMeasure::Range, None, 20)
+    }
+
+    fn backtest(strategy: Strategy, commission: f64, slippage: f64) -> Backtest {
+        Backtest {
+            strategy,
+            capital: 1000.0,
+            commission,
+            slippage,
+        }
+    }
+
+    /// With a fast SMA of 1 and a slow one of 2, the stock is held after every rising close
+    const CROSSOVER: Strategy = Strategy::SmaCrossover { fast: 1, slow: 2 };
+    const RISING_AND_FALLING: [(f64, f64); 6] = [
+        (10.0, 10.0),
+        (10.0, 11.0),
+        (11.0, 12.0),
+        (12.0, 11.0),
+        (12.0, 13.0),
+        (13.0, 12.5),
+    ];
+
+    #[test]
+    fn trades_fill_at_the_open_after_the_signal() {
+        let report = report(&RISING_AND_FALLING);
+        let result = backtest(CROSSOVER, 0.0, 0.0).run(&report, &volatility());
+
+        let trades: Vec<_> = result
+            .trades
+            .iter()
+            .map(|t| {
+                (
+                    t.entry_time.as_str(),
+                    t.entry_price,
+                    t.exit_time.as_str(),
+                    t.exit_price,
+                )
+            })
+            .collect();
+        assert_eq!(
+            trades,
+            [
+                // Bought at the open after the rise to 11, sold at the open after the fall to 11
+                ("2024-03-03", 11.0, "2024-03-05", 12.0),
+                // Still held after the last bar, so sold at its close
+                ("2024-03-06", 13.0, "2024-03-06", 12.5),
+            ]
+        );
+        assert_close(result.trades[0].shares, 1000.0 / 11.0);
+        assert_close(result.trades[0].profit, 12000.0 / 11.0 - 1000.0);
+        assert!(result.trades[1].profit < 0.0);
+
+        let final_equity = 12000.0 / 11.0 * 12.5 / 13.0;
+        let equity: Vec<f64> = result.equity.iter().map(|(_, value, _)| *value).collect();
+        assert_close(equity[equity.len() - 1], final_equity);
+        assert_close(result.equity[5].2, 1250.0);
+        assert_close(result.total_return, (final_equity / 1000.0 - 1.0) * 100.0);
+        assert_close(
+            result.cagr,
+            ((final_equity / 1000.0).powf(365.25 / 5.0) - 1.0) * 100.0,
+        );
+        assert_close(result.win_rate, 50.0);
+        // From 12000 / 11 at the close of 12 back to 1000 at the close of 11
+        assert_close(result.max_drawdown, 100.0 / 12.0);
+    }
+
+    #[test]
+    fn commission_and_slippage_are_paid_on_every_fill() {
+        let report = report(&RISING_AND_FALLING);
+        let result = backtest(CROSSOVER, 1.0, 1.0).run(&report, &volatility());
+
+        let trade = &result.trades[0];
+        assert_close(trade.entry_price, 11.0 * 1.01);
+        assert_close(trade.exit_price, 12.0 * 0.99);
+        assert_close(trade.shares, 999.0 / (11.0 * 1.01));
+        let cash = trade.shares * 12.0 * 0.99 - 1.0;
+        assert_close(trade.profit, cash - 1000.0);
+
+        let trade = &result.trades[1];
+        assert_close(trade.exit_price, 12.5 * 0.99);
+        let final_equity = (cash - 1.0) / (13.0 * 1.01) * 12.5 * 0.99 - 1.0;
+        assert_close(result.equity[5].1, final_equity);
+    }
+
+    #[test]
+    fn volatile_dips_are_held_for_a_number_of_bars() {
+        // Dips on the 2nd, 3rd and 6th bars, the 3rd one while already held
+        let report = report(&[
+            (100.0, 100.0),
+            (100.0, 90.0),
+            (90.0, 80.0),
+            (80.0, 82.0),
+            (82.0, 84.0),
+            (84.0, 80.0),
+        ]);
+        let strategy = Strategy::VolatileDip { hold: 2 };
+        assert_eq!(
+            strategy.positions(&report, &volatility()),
+            [false, true, true, false, false, true]
+        );
+
+        // Held over the two bars after the dip, and the last signal has no bar left to be filled on
+        let result = backtest(strategy, 0.0, 0.0).run(&report, &volatility());
+        assert_eq!(result.trades.len(), 1);
+        let trade = &result.trades[0];
+        assert_eq!(
+            (trade.entry_time.as_str(), trade.exit_time.as_str()),
+            ("2024-03-03", "2024-03-05")
+        );
+        assert_close(trade.return_percent, (82.0 / 90.0 - 1.0) * 100.0);
+        assert_close(result.win_rate, 0.0);
+    }
+
+    #[test]
+    fn strategies_are_parsed_with_their_defaults() {
+        assert_eq!(
+            "sma-crossover".parse(),
+            Ok(Strategy::SmaCrossover { fast: 20, slow: 50 })
+        );
+        assert_eq!(
+            "SMA-Crossover:10:30".parse(),
+            Ok(Strategy::SmaCrossover { fast: 10, slow: 30 })
+        );
+        assert_eq!(
+            "volatile-dip".parse(),
+            Ok(Strategy::VolatileDip { hold: 5 })
+        );
+        assert_eq!(
+            "volatile-dip:3".parse(),
+            Ok(Strategy::VolatileDip { hold: 3 })
+        );
+    }
+
+    #[test]
+    fn invalid_strategies_are_rejected() {
+        for strategy in [
+            "sma-crossover:50:20",
+            "sma-crossover:20:20",
+            "sma-crossover:0",
+            "volatile-dip:0",
+            "volatile-dip:-1",
+            "volatile-dip:x",
+            "momentum",
+        ] {
+            assert!(strategy.parse::<Strategy>().is_err(), "{}", strategy);
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+
+use crate::{
+    report::StockReport,
+    statistics::{self, correlation, covariance, mean},
+    timeframe::Interval,
+};
+
+/// How a stock moved against a benchmark, ex. SPY, over the bars both have quotes for
+pub struct BenchmarkComparison {
+    pub stock_name: String,
+    pub benchmark: String,
+    pub interval: Interval,
+    /// Sensitivity of the stock's returns to the benchmark's
+    pub beta: f64,
+    /// Annualized return of the stock not explained by beta, in percent
+    pub alpha: f64,
+    pub correlation: f64,
+    pub stock_return: f64,
+    pub benchmark_return: f64,
+    /// Closes of the stock and of the benchmark rebased to 100 at the first common bar
+    pub rebased: Vec<(NaiveDateTime, f64, f64)>,
+}
+
+impl BenchmarkComparison {
+    /// Returns None unless the stock and the benchmark share at least 3 bars
+    pub fn new(report: &StockReport, benchmark: &StockReport) -> Option<Self> {
+        let benchmark_closes: HashMap<NaiveDateTime, f64> =
+            benchmark.series.iter().copied().collect();
+        let common: Vec<(NaiveDateTime, f64, f64)> = report
+            .series
+            .iter()
+            .filter_map(|(time, close)| Some((*time, *close, *benchmark_closes.get(time)?)))
+            .collect();
+        if common.len() < 3 {
+            return None;
+        }
+
+        let stock_closes: Vec<f64> = common.iter().map(|(_, close, _)| *close).collect();
+        let benchmark_closes: Vec<f64> = common.iter().map(|(_, _, close)| *close).collect();
+        let stock_returns = statistics::returns(&stock_closes);
+        let benchmark_returns = statistics::returns(&benchmark_closes);
+        let benchmark_variance = covariance(&benchmark_returns, &benchmark_returns);
+        let beta = if benchmark_variance == 0.0 {
+            0.0
+        } else {
+            covariance(&stock_returns, &benchmark_returns) / benchmark_variance
+        };
+        let alpha = (mean(&stock_returns) - beta * mean(&benchmark_returns))
+            * report.interval.bars_per_year()
+            * 100.0;
+
+        let (_, first_stock, first_benchmark) = common[0];
+        let (_, last_stock, last_benchmark) = common[common.len() - 1];
+        Some(BenchmarkComparison {
+            stock_name: report.stock_name.clone(),
+            benchmark: benchmark.stock_name.clone(),
+            interval: report.interval,
+            beta,
+            alpha,
+            correlation: correlation(&stock_returns, &benchmark_returns),
+            stock_return: (last_stock / first_stock - 1.0) * 100.0,
+            benchmark_return: (last_benchmark / first_benchmark - 1.0) * 100.0,
+            rebased: common
+                .iter()
+                .map(|(time, stock, benchmark)| {
+                    (
+                        *time,
+                        100.0 * stock / first_stock,
+                        100.0 * benchmark / first_benchmark,
+                    )
+                })
+                .collect(),
+        })
+    }
+
+    /// Return of the stock above the benchmark's, in percentage points
+    pub fn relative_performance(&self) -> f64 {
+        self.stock_return - self.benchmark_return
+    }
+
+    pub fn print(&self) {
+        println!(
+            "{} vs {}:\nBeta: {:.2}\nAlpha: {:.2}%\nCorrelation: {:.2}\nReturn: {:.2}% (benchmark {:.2}%)\nRelative Performance: {:+.2}%",
+            self.stock_name,
+            self.benchmark,
+            self.beta,
+            self.alpha,
+            self.correlation,
+            self.stock_return,
+            self.benchmark_return,
+            self.relative_performance()
+        );
+    }
+}
@@ -0,0 +1,227 @@
+use std::{fs, path::PathBuf};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use yahoo_finance_api::Quote;
+
+use crate::{
+    provider::{ProviderError, QuoteProvider},
+    timeframe::Interval,
+};
+
+/// Quotes stored on disk for a single stock and interval, along with the date range that
+/// has already been requested from the provider
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    start: i64,
+    end: i64,
+    quotes: Vec<Quote>,
+}
+
+/// Wraps another provider and keeps fetched quotes in per-ticker JSON files,
+/// so that later runs only download the dates that are not cached yet
+pub struct CachedProvider {
+    inner: Box<dyn QuoteProvider>,
+    cache_dir: PathBuf,
+    offline: bool,
+}
+
+impl CachedProvider {
+    pub fn new(
+        inner: Box<dyn QuoteProvider>,
+        cache_dir: impl Into<PathBuf>,
+        offline: bool,
+    ) -> Self {
+        CachedProvider {
+            inner,
+            cache_dir: cache_dir.into(),
+            offline,
+        }
+    }
+
+    fn cache_file(&self, stock_name: &str, interval: Interval) -> PathBuf {
+        self.cache_dir
+            .join(format!("{}_{}.json", stock_name, interval.as_str()))
+    }
+
+    fn load(&self, stock_name: &str, interval: Interval) -> Option<CacheEntry> {
+        let contents = fs::read_to_string(self.cache_file(stock_name, interval)).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    fn save(
+        &self,
+        stock_name: &str,
+        interval: Interval,
+        entry: &CacheEntry,
+    ) -> Result<(), ProviderError> {
+        fs::create_dir_all(&self.cache_dir)?;
+        fs::write(
+            self.cache_file(stock_name, interval),
+            serde_json::to_string(entry)?,
+        )?;
+        Ok(())
+    }
+
+    fn fetch(
+        &self,
+        stock_name: &str,
+        start: i64,
+        end: i64,
+        interval: Interval,
+    ) -> Result<Vec<Quote>, ProviderError> {
+        self.inner.get_quote_history(
+            stock_name,
+            OffsetDateTime::from_unix_timestamp(start)?,
+            OffsetDateTime::from_unix_timestamp(end)?,
+            interval,
+        )
+    }
+}
+
+impl QuoteProvider for CachedProvider {
+    fn get_quote_history(
+        &self,
+        stock_name: &str,
+        start_date: OffsetDateTime,
+        end_date: OffsetDateTime,
+        interval: Interval,
+    ) -> Result<Vec<Quote>, ProviderError> {
+        let (start, end) = (start_date.unix_timestamp(), end_date.unix_timestamp());
+        let cached = self.load(stock_name, interval);
+
+        let entry = match (cached, self.offline) {
+            (Some(entry), true) => entry,
+            (None, true) => return Err(format!("No cached quotes for {}", stock_name).into()),
+            (None, false) => CacheEntry {
+                start,
+                end,
+                quotes: self.fetch(stock_name, start, end, interval)?,
+            },
+            (Some(mut entry), false) => {
+                // Only request the parts of the range that are missing from the cache.
+                // The tail is always refreshed from the last cached quote since the
+                // latest bar may have been incomplete when it was stored.
+                let mut fetched = Vec::new();
+                if start < entry.start {
+                    fetched.extend(self.fetch(stock_name, start, entry.start, interval)?);
+                }
+                if end > entry.end {
+                    let tail_start = entry
+                        .quotes
+                        .last()
+                        .map_or(entry.end, |quote| quote.timestamp as i64)
+                        .min(entry.end);
+                    fetched.extend(self.fetch(stock_name, tail_start, end, interval)?);
+                }
+                if !fetched.is_empty() {
+                    entry.quotes.retain(|quote| {
+                        !fetched.iter().any(|new| new.timestamp == quote.timestamp)
+                    });
+                    entry.quotes.extend(fetched);
+                    entry.quotes.sort_by_key(|quote| quote.timestamp);
+                }
+                entry.start = entry.start.min(start);
+                entry.end = entry.end.max(end);
+                entry
+            }
+        };
+
+        if !self.offline {
+            self.save(stock_name, interval, &entry)?;
+        }
+
+        Ok(entry
+            .quotes
+            .into_iter()
+            .filter(|quote| (start..=end).contains(&(quote.timestamp as i64)))
+            .collect())
+    }
+
+    fn get_latest_quote(&self, stock_name: &str) -> Result<Quote, ProviderError> {
+        if !self.offline {
+            return self.inner.get_latest_quote(stock_name);
+        }
+        // Use the most recent quote cached for any interval
+        Interval::value_variants()
+            .iter()
+            .filter_map(|interval| self.load(stock_name, *interval)?.quotes.pop())
+            .max_by_key(|quote| quote.timestamp)
+            .ok_or_else(|| format!("No cached quotes for {}", stock_name).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    /// Always returns the same bar with a close that changes on every request, like a bar that
+    /// was unfinished when it was cached, and records the requested ranges
+    struct RecordingProvider {
+        requests: Arc<Mutex<Vec<(i64, i64)>>>,
+    }
+
+    impl QuoteProvider for RecordingProvider {
+        fn get_quote_history(
+            &self,
+            _stock_name: &str,
+            start_date: OffsetDateTime,
+            end_date: OffsetDateTime,
+            _interval: Interval,
+        ) -> Result<Vec<Quote>, ProviderError> {
+            let mut requests = self.requests.lock().unwrap();
+            requests.push((start_date.unix_timestamp(), end_date.unix_timestamp()));
+            Ok(vec![Quote {
+                timestamp: 1100,
+                open: 1.0,
+                high: 1.0,
+                low: 1.0,
+                volume: 1,
+                close: requests.len() as f64,
+                adjclose: 1.0,
+            }])
+        }
+
+        fn get_latest_quote(&self, stock_name: &str) -> Result<Quote, ProviderError> {
+            Err(format!("No latest quote for {}", stock_name).into())
+        }
+    }
+
+    fn at(timestamp: i64) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(timestamp).unwrap()
+    }
+
+    #[test]
+    fn tail_is_refreshed_from_the_last_cached_quote() {
+        let cache_dir = std::env::temp_dir().join(format!("smm_cache_{}", std::process::id()));
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let cache = CachedProvider::new(
+            Box::new(RecordingProvider {
+                requests: Arc::clone(&requests),
+            }),
+            &cache_dir,
+            false,
+        );
+
+        // The first request caches a bar at 1100, before the end of the requested range
+        let quotes = cache
+            .get_quote_history("TEST", at(1000), at(5000), Interval::OneDay)
+            .unwrap();
+        assert_eq!(quotes.len(), 1);
+        assert_eq!(quotes[0].close, 1.0);
+
+        // Extending the range requests again from that bar, replacing it with the new close
+        let quotes = cache
+            .get_quote_history("TEST", at(1000), at(9000), Interval::OneDay)
+            .unwrap();
+        fs::remove_dir_all(&cache_dir).unwrap();
+
+        assert_eq!(*requests.lock().unwrap(), [(1000, 5000), (1100, 9000)]);
+        assert_eq!(quotes.len(), 1);
+        assert_eq!(quotes[0].timestamp, 1100);
+        assert_eq!(quotes[0].close, 2.0);
+    }
+}
@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use clap::ValueEnum;
+use yahoo_finance_api::Quote;
+
+use crate::{
+    api::{self, ApiError, Requested},
+    benchmark::BenchmarkComparison,
+    correlation::CorrelationMatrix,
+    plot::{render_chart, Chart, ChartFormat, ChartKind, PriceChart},
+    portfolio::PortfolioReport,
+    provider::fetch_concurrently,
+    server::Dashboard,
+    timeframe::{Interval, Window},
+};
+
+/// Renders `/chart/{ticker}`, `/chart/comparison`, `/chart/correlation`, `/chart/portfolio`,
+/// `/chart/equity/{ticker}` or `/chart/benchmark/{ticker}` as a PNG image, SVG image or
+/// interactive HTML page depending on the `.png`, `.svg` or `.html` extension. The window and
+/// interval are read from the same query parameters as the API, along with `volatile`, `chart`
+/// (line or candlestick) and `tickers` for the comparison and correlation charts.
+pub fn handle(
+    endpoint: &str,
+    query: &str,
+    dashboard: &Dashboard,
+) -> Result<(ChartFormat, Vec<u8>), ApiError> {
+    let (name, format) = endpoint
+        .rsplit_once('.')
+        .and_then(|(name, extension)| Some((name, ChartFormat::from_extension(extension)?)))
+        .ok_or_else(|| ApiError::new(404, "Not Found"))?;
+    let params = api::parse_params(query);
+    let (window, interval) = api::parse_window(&params, dashboard)?;
+    let tickers = || match params.get("tickers") {
+        Some(tickers) => tickers.split(',').map(str::to_owned).collect(),
+        None => dashboard.stock_names.clone(),
+    };
+
+    let chart = if name == "comparison" {
+        render_comparison(&tickers(), window, interval, format, dashboard)
+    } else if name == "correlation" {
+        render_correlation(&tickers(), window, interval, format, dashboard)
+    } else if name == "portfolio" {
+        render_portfolio(format, dashboard)
+    } else if let Some(ticker) = name.strip_prefix("equity/") {
+        render_equity(&api::decode(ticker), window, interval, format, dashboard)
+    } else if let Some(ticker) = name.strip_prefix("benchmark/") {
+        render_benchmark(&api::decode(ticker), window, interval, format, dashboard)
+    } else {
+        let kind = match params.get("chart") {
+            Some(kind) => ChartKind::from_str(kind, true)
+                .map_err(|_| ApiError::new(400, format!("Invalid chart {}", kind)))?,
+            None => ChartKind::Line,
+        };
+        let show_volatility = match params.get("volatile").map(String::as_str) {
+            Some("true") => true,
+            Some("false") | None => false,
+            Some(value) => {
+                return Err(ApiError::new(
+                    400,
+                    format!("Invalid volatile {}, expected true or false", value),
+                ))
+            }
+        };
+        let ticker = api::decode(name);
+        render_prices(
+            &ticker,
+            window,
+            interval,
+            kind,
+            show_volatility,
+            format,
+            dashboard,
+        )
+    };
+    chart.map(|chart| (format, chart))
+}
+
+/// Renders the prices of a stock with its indicators and volume
+pub fn render_prices(
+    ticker: &str,
+    window: Window,
+    interval: Interval,
+    kind: ChartKind,
+    show_volatility: bool,
+    format: ChartFormat,
+    dashboard: &Dashboard,
+) -> Result<Vec<u8>, ApiError> {
+    let requested = api::fetch_report(ticker, window, interval, dashboard)?;
+    let (min_date, max_date) = axis(&requested)?;
+    let chart = Chart::Prices {
+        min_date,
+        max_date,
+        report: &requested.report,
+        layers: PriceChart {
+            kind,
+            show_volatility,
+            indicators: &dashboard.monitor.indicators,
+        },
+    };
+    render_chart(&chart, format, &dashboard.style).map_err(render_error)
+}
+
+/// The time axis of the requested window, rejected when it has no width to draw the chart over
+fn axis(requested: &Requested) -> Result<(NaiveDateTime, NaiveDateTime), ApiError> {
+    let (min_date, max_date) = requested.window.axis(
+        requested.interval,
+        requested.dates,
+        requested.report.series[0].0,
+    );
+    if max_date <= min_date {
+        return Err(ApiError::new(
+            400,
+            "The chart needs a start date before its end date",
+        ));
+    }
+    Ok((min_date, max_date))
+}
+
+fn render_error(error: Box<dyn std::error::Error>) -> ApiError {
+    ApiError::new(500, format!("Could not render the chart: {}", error))
+}
+
+/// Fetches the stocks concurrently
+fn fetch_reports(
+    tickers: &[String],
+    window: Window,
+    interval: Interval,
+    dashboard: &Dashboard,
+) -> Result<Vec<Requested>, ApiError> {
+    fetch_concurrently(tickers, |ticker| {
+        api::fetch_report(ticker, window, interval, dashboard)
+    })
+}
+
+/// Renders the percent change of several stocks
+pub fn render_comparison(
+    tickers: &[String],
+    window: Window,
+    interval: Interval,
+    format: ChartFormat,
+    dashboard: &Dashboard,
+) -> Result<Vec<u8>, ApiError> {
+    let requests = fetch_reports(tickers, window, interval, dashboard)?;
+
+    // Every stock shares the time axis, spanning all of their windows
+    let axes: Vec<_> = requests
+        .iter()
+        .map(|r| r.window.axis(r.interval, r.dates, r.report.series[0].0))
+        .collect();
+    let (min_date, max_date) = match (
+        axes.iter().map(|(min, _)| *min).min(),
+        axes.iter().map(|(_, max)| *max).max(),
+    ) {
+        (Some(min_date), Some(max_date)) => (min_date, max_date),
+        _ => return Err(ApiError::new(400, "No stock symbols to compare")),
+    };
+    let reports: Vec<_> = requests.into_iter().map(|r| r.report).collect();
+    let chart = Chart::Comparison {
+        min_date,
+        max_date,
+        reports: &reports,
+    };
+    render_chart(&chart, format, &dashboard.style).map_err(render_error)
+}
+
+/// Renders the correlation of the returns of several stocks as a heatmap
+pub fn render_correlation(
+    tickers: &[String],
+    window: Window,
+    interval: Interval,
+    format: ChartFormat,
+    dashboard: &Dashboard,
+) -> Result<Vec<u8>, ApiError> {
+    let reports: Vec<_> = fetch_reports(tickers, window, interval, dashboard)?
+        .into_iter()
+        .map(|r| r.report)
+        .collect();
+    if reports.len() < 2 {
+        return Err(ApiError::new(
+            400,
+            "At least 2 stock symbols are needed to correlate",
+        ));
+    }
+    let matrix = CorrelationMatrix::new(&reports);
+    render_chart(&Chart::Correlation(&matrix), format, &dashboard.style).map_err(render_error)
+}
+
+/// Renders the value of the portfolio since its first purchase, fetching its stocks concurrently
+pub fn render_portfolio(format: ChartFormat, dashboard: &Dashboard) -> Result<Vec<u8>, ApiError> {
+    let portfolio = dashboard
+        .portfolio
+        .as_ref()
+        .ok_or_else(|| ApiError::new(404, "No portfolio was loaded"))?;
+    let monitor = &dashboard.monitor;
+    let tickers = portfolio.tickers();
+    let (start_date, end_date) = (portfolio.start_date, monitor.today(&tickers));
+    let histories: HashMap<String, Vec<Quote>> = fetch_concurrently(&tickers, |ticker| {
+        monitor
+            .provider
+            .get_quote_history(ticker, start_date, end_date, Interval::OneDay)
+            .map(|quotes| (ticker.to_owned(), quotes))
+            .map_err(|error| ApiError::new(502, error.to_string()))
+    })?
+    .into_iter()
+    .collect();
+    let report = PortfolioReport::new(portfolio, &histories)
+        .ok_or_else(|| ApiError::new(502, "No quotes found for every stock of the portfolio"))?;
+    render_chart(&Chart::Portfolio(&report), format, &dashboard.style).map_err(render_error)
+}
+
+/// Renders the equity curve of the backtested strategy over the requested window
+pub fn render_equity(
+    ticker: &str,
+    window: Window,
+    interval: Interval,
+    format: ChartFormat,
+    dashboard: &Dashboard,
+) -> Result<Vec<u8>, ApiError> {
+    let backtest = dashboard
+        .backtest
+        .ok_or_else(|| ApiError::new(404, "No backtest strategy was selected"))?;
+    let requested = api::fetch_report(ticker, window, interval, dashboard)?;
+    let result = backtest.run(&requested.report, &dashboard.monitor.volatility);
+    let (min_date, max_date) = axis(&requested)?;
+    let chart = Chart::Equity {
+        min_date,
+        max_date,
+        result: &result,
+    };
+    render_chart(&chart, format, &dashboard.style).map_err(render_error)
+}
+
+/// Renders the stock against the benchmark over the requested window, fetching both concurrently
+pub fn render_benchmark(
+    ticker: &str,
+    window: Window,
+    interval: Interval,
+    format: ChartFormat,
+    dashboard: &Dashboard,
+) -> Result<Vec<u8>, ApiError> {
+    let benchmark = dashboard
+        .benchmark
+        .as_ref()
+        .ok_or_else(|| ApiError::new(404, "No benchmark was selected"))?;
+    let requests = fetch_concurrently(&[ticker.to_owned(), benchmark.clone()], |ticker| {
+        api::fetch_report(ticker, window, interval, dashboard)
+    })?;
+    let (requested, benchmark) = (&requests[0], &requests[1]);
+    let comparison = BenchmarkComparison::new(&requested.report, &benchmark.report)
+        .ok_or_else(|| ApiError::new(422, "The stock and the benchmark share fewer than 3 bars"))?;
+    let (min_date, max_date) = axis(requested)?;
+    let chart = Chart::Benchmark {
+        min_date,
+        max_date,
+        comparison: &comparison,
+    };
+    render_chart(&chart, format, &dashboard.style).map_err(render_error)
+}
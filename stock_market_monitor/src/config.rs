@@ -0,0 +1,78 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Deserializer};
+
+use crate::{
+    alerts::AlertConfig,
+    plot::ChartStyle,
+    timeframe::{Interval, Range},
+    volatility::VolatilityMeasure,
+};
+
+/// File read when --config is not given, if it exists
+pub const DEFAULT_CONFIG_FILE: &str = "stock_market_monitor.toml";
+
+/// Settings shared through a TOML file, each overridden by its command line option
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Named lists of tickers, ex. `tech = ["AAPL", "MSFT"]`
+    pub watchlists: HashMap<String, Vec<String>>,
+    /// Watchlist charted when no tickers or --watchlist are given
+    pub watchlist: Option<String>,
+    #[serde(deserialize_with = "deserialize_value_enum")]
+    pub range: Option<Range>,
+    #[serde(deserialize_with = "deserialize_value_enum")]
+    pub interval: Option<Interval>,
+    #[serde(deserialize_with = "deserialize_value_enum")]
+    pub volatility: Option<VolatilityMeasure>,
+    pub volatility_threshold: Option<f64>,
+    pub volatility_window: Option<usize>,
+    pub cache_dir: Option<PathBuf>,
+    pub chart: ChartStyle,
+    pub server: ServerConfig,
+    /// Alert rules and their sinks, as in the --alerts file
+    pub alerts: Option<AlertConfig>,
+}
+
+/// Where the dashboard is served
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub address: Option<String>,
+    pub port: Option<u16>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let config: Config = toml::from_str(&fs::read_to_string(path)?)?;
+        config.chart.validate()?;
+        if let Some(watchlist) = &config.watchlist {
+            config.watchlist(watchlist)?;
+        }
+        Ok(config)
+    }
+
+    /// Tickers of the named watchlist
+    pub fn watchlist(&self, name: &str) -> Result<&[String], String> {
+        self.watchlists
+            .get(name)
+            .map(Vec::as_slice)
+            .ok_or_else(|| format!("There is no watchlist named {}", name))
+    }
+}
+
+/// Reads a range, interval or volatility measure by the name used on the command line
+fn deserialize_value_enum<'de, D: Deserializer<'de>, T: ValueEnum>(
+    deserializer: D,
+) -> Result<Option<T>, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    T::from_str(&name, true)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
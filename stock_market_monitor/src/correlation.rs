@@ -0,0 +1,93 @@
+use std::{collections::HashMap, path::Path};
+
+use chrono::NaiveDateTime;
+
+use crate::{report::StockReport, statistics};
+
+/// Pairwise correlation of the returns of several stocks
+pub struct CorrelationMatrix {
+    pub stock_names: Vec<String>,
+    /// Row and column i are the stock i. None when two stocks share fewer than 3 bars.
+    pub values: Vec<Vec<Option<f64>>>,
+}
+
+impl CorrelationMatrix {
+    /// Correlates the returns of every pair of stocks over the bars both have quotes for
+    pub fn new(reports: &[StockReport]) -> Self {
+        let closes: Vec<HashMap<NaiveDateTime, f64>> = reports
+            .iter()
+            .map(|report| report.series.iter().copied().collect())
+            .collect();
+        let values = reports
+            .iter()
+            .enumerate()
+            .map(|(i, report)| {
+                (0..reports.len())
+                    .map(|j| {
+                        if i == j {
+                            return Some(1.0);
+                        }
+                        // Align the closes of both stocks on their common bar times
+                        let (a, b): (Vec<f64>, Vec<f64>) = report
+                            .series
+                            .iter()
+                            .filter_map(|(time, close)| Some((*close, *closes[j].get(time)?)))
+                            .unzip();
+                        (a.len() >= 3).then(|| {
+                            statistics::correlation(
+                                &statistics::returns(&a),
+                                &statistics::returns(&b),
+                            )
+                        })
+                    })
+                    .collect()
+            })
+            .collect();
+
+        CorrelationMatrix {
+            stock_names: reports
+                .iter()
+                .map(|report| report.stock_name.clone())
+                .collect(),
+            values,
+        }
+    }
+
+    pub fn print(&self) {
+        println!("Correlation of Returns:");
+        print!("{:<8}", "");
+        for stock_name in &self.stock_names {
+            print!(" {:>8}", stock_name);
+        }
+        println!();
+        for (stock_name, row) in self.stock_names.iter().zip(&self.values) {
+            print!("{:<8}", stock_name);
+            for value in row {
+                match value {
+                    Some(value) => print!(" {:>8.2}", value),
+                    None => print!(" {:>8}", "-"),
+                }
+            }
+            println!();
+        }
+    }
+
+    /// Writes the matrix with a header row and a first column of tickers, leaving the pairs
+    /// without enough common bars empty
+    pub fn write_csv(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let mut writer = csv::Writer::from_path(path)?;
+        writer.write_record(
+            std::iter::once("ticker").chain(self.stock_names.iter().map(String::as_str)),
+        )?;
+        for (stock_name, row) in self.stock_names.iter().zip(&self.values) {
+            writer.write_record(
+                std::iter::once(stock_name.clone()).chain(
+                    row.iter()
+                        .map(|value| value.map(|v| v.to_string()).unwrap_or_default()),
+                ),
+            )?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
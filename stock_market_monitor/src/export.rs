@@ -0,0 +1,383 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use arrow::{
+    array::{ArrayRef, BooleanArray, Float64Array, StringArray, TimestampSecondArray, UInt64Array},
+    datatypes::{DataType, Field, Schema, TimeUnit},
+    ipc::writer::FileWriter,
+    record_batch::RecordBatch,
+};
+use chrono::NaiveDateTime;
+use clap::ValueEnum;
+use parquet::arrow::ArrowWriter;
+use serde::{ser::SerializeMap, Serialize, Serializer};
+use yahoo_finance_api::Quote;
+
+use crate::{indicators::Indicator, report::StockReport};
+
+/// File format of the exported bars
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum ExportFormat {
+    /// Comma separated values with a header row
+    Csv,
+    /// One JSON object per line
+    Jsonl,
+    /// Apache Parquet
+    Parquet,
+    /// Apache Arrow IPC file, also known as Feather
+    Arrow,
+}
+
+impl ExportFormat {
+    /// Format matching the extension of the file, ex. `.parquet`
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "csv" => Some(ExportFormat::Csv),
+            "jsonl" | "ndjson" => Some(ExportFormat::Jsonl),
+            "parquet" => Some(ExportFormat::Parquet),
+            "arrow" | "feather" => Some(ExportFormat::Arrow),
+            _ => None,
+        }
+    }
+}
+
+/// A bar of a stock as exported for analysis
+#[derive(Serialize)]
+struct ExportedBar<'a> {
+    ticker: &'a str,
+    time: String,
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+    volume: u64,
+    adjclose: f64,
+    volatile: bool,
+    #[serde(flatten)]
+    indicators: IndicatorValues<'a>,
+}
+
+/// The indicator values of a bar by column name, None until enough bars are available
+struct IndicatorValues<'a> {
+    names: &'a [String],
+    values: Vec<Option<f64>>,
+}
+
+impl Serialize for IndicatorValues<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.names.len()))?;
+        for (name, value) in self.names.iter().zip(&self.values) {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+/// The bars of every stock one after the other, along with the indicators computed over them
+pub struct Export<'a> {
+    tickers: Vec<&'a str>,
+    times: Vec<NaiveDateTime>,
+    quotes: Vec<&'a Quote>,
+    volatile: Vec<bool>,
+    time_format: &'static str,
+    indicator_names: Vec<String>,
+    /// One column per indicator line, ex. the three Bollinger Bands
+    indicator_values: Vec<Vec<Option<f64>>>,
+}
+
+impl<'a> Export<'a> {
+    pub fn new(reports: &'a [StockReport], indicators: &[Indicator]) -> Self {
+        let mut export = Export {
+            tickers: Vec::new(),
+            times: Vec::new(),
+            quotes: Vec::new(),
+            volatile: Vec::new(),
+            time_format: reports
+                .first()
+                .map_or("%Y-%m-%d", |report| report.interval.time_format()),
+            indicator_names: Vec::new(),
+            indicator_values: Vec::new(),
+        };
+        for report in reports {
+            let volatile_times: HashSet<NaiveDateTime> =
+                report.volatile_days.iter().map(|(time, _)| *time).collect();
+            for (time, quote) in &report.quotes {
+                export.tickers.push(&report.stock_name);
+                export.times.push(*time);
+                export.quotes.push(quote);
+                export.volatile.push(volatile_times.contains(time));
+            }
+
+            // Every stock has the same indicator columns, appended to the previous stocks'
+            let quotes = report.raw_quotes();
+            let columns = indicators
+                .iter()
+                .flat_map(|indicator| indicator.columns(&quotes));
+            for (i, (name, values)) in columns.enumerate() {
+                if i == export.indicator_names.len() {
+                    export.indicator_names.push(name);
+                    export.indicator_values.push(Vec::new());
+                }
+                export.indicator_values[i].extend(values);
+            }
+        }
+        export
+    }
+
+    /// Number of exported bars over every stock
+    pub fn bar_count(&self) -> usize {
+        self.quotes.len()
+    }
+
+    pub fn write(
+        &self,
+        path: &Path,
+        format: ExportFormat,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match format {
+            ExportFormat::Csv => self.write_csv(path),
+            ExportFormat::Jsonl => self.write_jsonl(path),
+            ExportFormat::Parquet => self.write_parquet(path),
+            ExportFormat::Arrow => self.write_arrow(path),
+        }
+    }
+
+    fn bar(&self, i: usize) -> ExportedBar<'_> {
+        let quote = self.quotes[i];
+        ExportedBar {
+            ticker: self.tickers[i],
+            time: self.times[i].format(self.time_format).to_string(),
+            open: quote.open,
+            high: quote.high,
+            low: quote.low,
+            close: quote.close,
+            volume: quote.volume,
+            adjclose: quote.adjclose,
+            volatile: self.volatile[i],
+            indicators: IndicatorValues {
+                names: &self.indicator_names,
+                values: self
+                    .indicator_values
+                    .iter()
+                    .map(|column| column[i])
+                    .collect(),
+            },
+        }
+    }
+
+    /// Writes one row per stock and bar, leaving the indicators empty until they have a value
+    fn write_csv(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let mut writer = csv::Writer::from_path(path)?;
+        let header = [
+            "ticker", "time", "open", "high", "low", "close", "volume", "adjclose", "volatile",
+        ];
+        writer.write_record(
+            header
+                .into_iter()
+                .chain(self.indicator_names.iter().map(String::as_str)),
+        )?;
+        for i in 0..self.bar_count() {
+            // The csv crate cannot serialize the flattened indicators, so the row is built here
+            let bar = self.bar(i);
+            let fields = [
+                bar.ticker.to_owned(),
+                bar.time,
+                bar.open.to_string(),
+                bar.high.to_string(),
+                bar.low.to_string(),
+                bar.close.to_string(),
+                bar.volume.to_string(),
+                bar.adjclose.to_string(),
+                bar.volatile.to_string(),
+            ];
+            writer.write_record(
+                fields.into_iter().chain(
+                    bar.indicators
+                        .values
+                        .iter()
+                        .map(|value| value.map(|v| v.to_string()).unwrap_or_default()),
+                ),
+            )?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes one JSON object per stock and bar, with null indicators until they have a value
+    fn write_jsonl(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        for i in 0..self.bar_count() {
+            serde_json::to_writer(&mut writer, &self.bar(i))?;
+            writeln!(writer)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// The bars as a single Arrow record batch, with the bar times as timestamps
+    fn record_batch(&self) -> Result<RecordBatch, Box<dyn std::error::Error>> {
+        let prices = |price: fn(&Quote) -> f64| -> ArrayRef {
+            Arc::new(Float64Array::from_iter_values(
+                self.quotes.iter().map(|quote| price(quote)),
+            ))
+        };
+        let mut fields = vec![
+            Field::new("ticker", DataType::Utf8, false),
+            Field::new("time", DataType::Timestamp(TimeUnit::Second, None), false),
+            Field::new("open", DataType::Float64, false),
+            Field::new("high", DataType::Float64, false),
+            Field::new("low", DataType::Float64, false),
+            Field::new("close", DataType::Float64, false),
+            Field::new("volume", DataType::UInt64, false),
+            Field::new("adjclose", DataType::Float64, false),
+            Field::new("volatile", DataType::Boolean, false),
+        ];
+        let mut columns: Vec<ArrayRef> = vec![
+            Arc::new(StringArray::from(self.tickers.clone())),
+            Arc::new(TimestampSecondArray::from_iter_values(
+                self.times.iter().map(|time| time.and_utc().timestamp()),
+            )),
+            prices(|quote| quote.open),
+            prices(|quote| quote.high),
+            prices(|quote| quote.low),
+            prices(|quote| quote.close),
+            Arc::new(UInt64Array::from_iter_values(
+                self.quotes.iter().map(|quote| quote.volume),
+            )),
+            prices(|quote| quote.adjclose),
+            Arc::new(BooleanArray::from(self.volatile.clone())),
+        ];
+        for (name, values) in self.indicator_names.iter().zip(&self.indicator_values) {
+            fields.push(Field::new(name, DataType::Float64, true));
+            columns.push(Arc::new(Float64Array::from(values.clone())));
+        }
+        Ok(RecordBatch::try_new(
+            Arc::new(Schema::new(fields)),
+            columns,
+        )?)
+    }
+
+    fn write_parquet(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let batch = self.record_batch()?;
+        let mut writer = ArrowWriter::try_new(File::create(path)?, batch.schema(), None)?;
+        writer.write(&batch)?;
+        writer.close()?;
+        Ok(())
+    }
+
+    fn write_arrow(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let batch = self.record_batch()?;
+        let mut writer = FileWriter::try_new(File::create(path)?, &batch.schema())?;
+        writer.write(&batch)?;
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{
+        timeframe::Interval,
+        volatility::{Volatility, VolatilityMeasure},
+    };
+
+    fn report(stock_name: &str, closes: &[f64]) -> StockReport {
+        let quotes: Vec<Quote> = closes
+            .iter()
+            .enumerate()
+            .map(|(day, close)| Quote {
+                timestamp: 1_709_251_200 + day as u64 * 86_400,
+                open: *close,
+                high: *close,
+                low: *close,
+                volume: 1000,
+                close: *close,
+                adjclose: *close,
+            })
+            .collect();
+        let volatility = Volatility::new(VolatilityMeasure::Range, None, 20);
+        StockReport::new(stock_name, &quotes, Interval::OneDay, &volatility).unwrap()
+    }
+
+    fn export_to(name: &str, format: ExportFormat) -> String {
+        let reports = [
+            report("AAA", &[10.0, 11.0, 12.0]),
+            report("BBB", &[20.0, 19.0, 21.0]),
+        ];
+        let indicators: Vec<Indicator> = ["bb:2:2", "macd:1:2:1"]
+            .iter()
+            .map(|indicator| indicator.parse().unwrap())
+            .collect();
+        let export = Export::new(&reports, &indicators);
+        assert_eq!(export.bar_count(), 6);
+
+        let path = std::env::temp_dir().join(format!("smm_export_{}_{}", std::process::id(), name));
+        export.write(&path, format).unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        written
+    }
+
+    const INDICATOR_COLUMNS: [&str; 6] = [
+        "bb_2_2_lower",
+        "bb_2_2_middle",
+        "bb_2_2_upper",
+        "macd_1_2_1",
+        "macd_1_2_1_signal",
+        "macd_1_2_1_histogram",
+    ];
+
+    #[test]
+    fn csv_has_a_column_per_indicator_line() {
+        let csv = export_to("bars.csv", ExportFormat::Csv);
+        let rows: Vec<Vec<&str>> = csv.lines().map(|line| line.split(',').collect()).collect();
+        assert_eq!(rows.len(), 7);
+
+        let mut header = vec![
+            "ticker", "time", "open", "high", "low", "close", "volume", "adjclose", "volatile",
+        ];
+        header.extend(INDICATOR_COLUMNS);
+        assert_eq!(rows[0], header);
+
+        // Each stock's indicators start over, empty on its first bar
+        for row in [&rows[1], &rows[4]] {
+            assert!(row[9..].iter().all(|cell| cell.is_empty()), "{:?}", row);
+        }
+        assert_eq!(rows[4][..2], ["BBB", "2024-03-01"]);
+        for row in &rows[2..4] {
+            assert!(row[9..].iter().all(|cell| !cell.is_empty()), "{:?}", row);
+        }
+        // The middle band of the 2 bar period is the average of the last 2 closes
+        assert_eq!(rows[3][10], "11.5");
+        assert_eq!(rows[6][10], "20");
+    }
+
+    #[test]
+    fn jsonl_has_null_indicators_until_they_have_a_value() {
+        let jsonl = export_to("bars.jsonl", ExportFormat::Jsonl);
+        let bars: Vec<serde_json::Value> = jsonl
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(bars.len(), 6);
+
+        for (i, bar) in bars.iter().enumerate() {
+            let first_bar = i % 3 == 0;
+            assert_eq!(bar["ticker"], if i < 3 { "AAA" } else { "BBB" });
+            for column in INDICATOR_COLUMNS {
+                assert_eq!(bar[column].is_null(), first_bar, "{} of bar {}", column, i);
+            }
+        }
+        assert_eq!(bars[2]["bb_2_2_middle"], 11.5);
+        assert_eq!(bars[5]["close"], 21.0);
+    }
+}
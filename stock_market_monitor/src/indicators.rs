@@ -0,0 +1,487 @@
+use std::{fmt, str::FromStr};
+
+use yahoo_finance_api::Quote;
+
+// Every indicator returns one value per quote, None until enough quotes are
+// available to compute it.
+
+/// Simple moving average of any series of values
+pub fn moving_average(values: &[f64], period: usize) -> Vec<Option<f64>> {
+    let mut averages = vec![None; values.len()];
+    if period == 0 {
+        return averages;
+    }
+    for end in period..=values.len() {
+        averages[end - 1] = Some(values[end - period..end].iter().sum::<f64>() / period as f64);
+    }
+    averages
+}
+
+/// Exponential moving average of any series of values, seeded with the simple
+/// average of the first `period` values. Missing values at the start are skipped.
+pub fn exponential_average(values: &[Option<f64>], period: usize) -> Vec<Option<f64>> {
+    let mut averages = vec![None; values.len()];
+    let first = match values.iter().position(|v| v.is_some()) {
+        Some(first) if period > 0 && values.len() >= first + period => first,
+        _ => return averages,
+    };
+    let values: Vec<f64> = values[first..].iter().map(|v| v.unwrap_or(0.0)).collect();
+    let alpha = 2.0 / (period as f64 + 1.0);
+    let mut average = values[..period].iter().sum::<f64>() / period as f64;
+    averages[first + period - 1] = Some(average);
+    for (i, value) in values.iter().enumerate().skip(period) {
+        average += alpha * (value - average);
+        averages[first + i] = Some(average);
+    }
+    averages
+}
+
+/// Smoothing used by RSI and ATR, an exponential average with alpha = 1 / period
+fn wilder_average(values: &[f64], period: usize) -> Vec<Option<f64>> {
+    let mut averages = vec![None; values.len()];
+    if period == 0 || values.len() < period {
+        return averages;
+    }
+    let mut average = values[..period].iter().sum::<f64>() / period as f64;
+    averages[period - 1] = Some(average);
+    for (i, value) in values.iter().enumerate().skip(period) {
+        average = (average * (period as f64 - 1.0) + value) / period as f64;
+        averages[i] = Some(average);
+    }
+    averages
+}
+
+fn closes(quotes: &[Quote]) -> Vec<f64> {
+    quotes.iter().map(|quote| quote.close).collect()
+}
+
+/// Simple moving average of the closing prices
+pub fn sma(quotes: &[Quote], period: usize) -> Vec<Option<f64>> {
+    moving_average(&closes(quotes), period)
+}
+
+/// Exponential moving average of the closing prices
+pub fn ema(quotes: &[Quote], period: usize) -> Vec<Option<f64>> {
+    let closes: Vec<Option<f64>> = quotes.iter().map(|quote| Some(quote.close)).collect();
+    exponential_average(&closes, period)
+}
+
+/// Relative strength index of the closing prices, between 0 and 100
+pub fn rsi(quotes: &[Quote], period: usize) -> Vec<Option<f64>> {
+    let mut values = vec![None; quotes.len()];
+    if quotes.len() < 2 {
+        return values;
+    }
+    // Gains and losses start at the second quote since they need a previous close
+    let changes: Vec<f64> = quotes.windows(2).map(|w| w[1].close - w[0].close).collect();
+    let gains: Vec<f64> = changes.iter().map(|c| c.max(0.0)).collect();
+    let losses: Vec<f64> = changes.iter().map(|c| (-c).max(0.0)).collect();
+    let average_gains = wilder_average(&gains, period);
+    let average_losses = wilder_average(&losses, period);
+    for (i, (gain, loss)) in average_gains.iter().zip(&average_losses).enumerate() {
+        if let (Some(gain), Some(loss)) = (gain, loss) {
+            // A flat series has neither gains nor losses and is neutral
+            values[i + 1] = Some(if *gain == 0.0 && *loss == 0.0 {
+                50.0
+            } else if *loss == 0.0 {
+                100.0
+            } else {
+                100.0 - 100.0 / (1.0 + gain / loss)
+            });
+        }
+    }
+    values
+}
+
+/// A single point of the MACD indicator
+#[derive(Clone, Copy, Debug)]
+pub struct MacdPoint {
+    pub macd: f64,
+    pub signal: f64,
+    pub histogram: f64,
+}
+
+/// Moving average convergence divergence of the closing prices
+pub fn macd(quotes: &[Quote], fast: usize, slow: usize, signal: usize) -> Vec<Option<MacdPoint>> {
+    let fast_ema = ema(quotes, fast);
+    let slow_ema = ema(quotes, slow);
+    let macd_line: Vec<Option<f64>> = fast_ema
+        .iter()
+        .zip(&slow_ema)
+        .map(|(fast, slow)| Some((*fast)? - (*slow)?))
+        .collect();
+    let signal_line = exponential_average(&macd_line, signal);
+    macd_line
+        .iter()
+        .zip(&signal_line)
+        .map(|(macd, signal)| {
+            let (macd, signal) = ((*macd)?, (*signal)?);
+            Some(MacdPoint {
+                macd,
+                signal,
+                histogram: macd - signal,
+            })
+        })
+        .collect()
+}
+
+/// A single point of the Bollinger Bands
+#[derive(Clone, Copy, Debug)]
+pub struct BollingerPoint {
+    pub lower: f64,
+    pub middle: f64,
+    pub upper: f64,
+}
+
+/// Simple moving average of the closing prices with bands `deviations` standard deviations away
+pub fn bollinger_bands(
+    quotes: &[Quote],
+    period: usize,
+    deviations: f64,
+) -> Vec<Option<BollingerPoint>> {
+    let closes = closes(quotes);
+    moving_average(&closes, period)
+        .iter()
+        .enumerate()
+        .map(|(i, middle)| {
+            let middle = (*middle)?;
+            let window = &closes[i + 1 - period..=i];
+            let variance = window.iter().map(|c| (c - middle).powi(2)).sum::<f64>() / period as f64;
+            let width = deviations * variance.sqrt();
+            Some(BollingerPoint {
+                lower: middle - width,
+                middle,
+                upper: middle + width,
+            })
+        })
+        .collect()
+}
+
+/// Average true range, the smoothed size of each bar including gaps from the previous close
+pub fn atr(quotes: &[Quote], period: usize) -> Vec<Option<f64>> {
+    let true_ranges: Vec<f64> = quotes
+        .iter()
+        .enumerate()
+        .map(|(i, quote)| match i {
+            0 => quote.high - quote.low,
+            _ => {
+                let previous_close = quotes[i - 1].close;
+                (quote.high - quote.low)
+                    .max((quote.high - previous_close).abs())
+                    .max((quote.low - previous_close).abs())
+            }
+        })
+        .collect();
+    wilder_average(&true_ranges, period)
+}
+
+/// An indicator selected on the command line, ex. `sma:20`, `bb:20:2` or `macd`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Indicator {
+    Sma(usize),
+    Ema(usize),
+    Rsi(usize),
+    Macd {
+        fast: usize,
+        slow: usize,
+        signal: usize,
+    },
+    Bollinger {
+        period: usize,
+        deviations: f64,
+    },
+    Atr(usize),
+}
+
+impl Indicator {
+    /// Indicators drawn on top of the prices rather than in their own panel
+    pub fn is_overlay(&self) -> bool {
+        matches!(
+            self,
+            Indicator::Sma(_) | Indicator::Ema(_) | Indicator::Bollinger { .. }
+        )
+    }
+
+    /// Describes the value of the indicator at the last quote
+    pub fn latest(&self, quotes: &[Quote]) -> String {
+        fn last<T: Copy>(values: Vec<Option<T>>) -> Option<T> {
+            values.last().copied().flatten()
+        }
+        let value = match *self {
+            Indicator::Sma(period) => last(sma(quotes, period)).map(|v| format!("{:.2}", v)),
+            Indicator::Ema(period) => last(ema(quotes, period)).map(|v| format!("{:.2}", v)),
+            Indicator::Rsi(period) => last(rsi(quotes, period)).map(|v| format!("{:.2}", v)),
+            Indicator::Macd { fast, slow, signal } => {
+                last(macd(quotes, fast, slow, signal)).map(|p| {
+                    format!(
+                        "{:.2} (signal {:.2}, histogram {:.2})",
+                        p.macd, p.signal, p.histogram
+                    )
+                })
+            }
+            Indicator::Bollinger { period, deviations } => {
+                last(bollinger_bands(quotes, period, deviations))
+                    .map(|b| format!("{:.2} / {:.2} / {:.2}", b.lower, b.middle, b.upper))
+            }
+            Indicator::Atr(period) => last(atr(quotes, period)).map(|v| format!("{:.2}", v)),
+        };
+        value.unwrap_or_else(|| "not enough quotes".to_owned())
+    }
+
+    /// Named values of the indicator at every quote, one column per line of the indicator,
+    /// ex. `sma_20` or `bb_20_2_lower`, `bb_20_2_middle` and `bb_20_2_upper`
+    pub fn columns(&self, quotes: &[Quote]) -> Vec<(String, Vec<Option<f64>>)> {
+        match *self {
+            Indicator::Sma(period) => vec![(format!("sma_{}", period), sma(quotes, period))],
+            Indicator::Ema(period) => vec![(format!("ema_{}", period), ema(quotes, period))],
+            Indicator::Rsi(period) => vec![(format!("rsi_{}", period), rsi(quotes, period))],
+            Indicator::Macd { fast, slow, signal } => {
+                let points = macd(quotes, fast, slow, signal);
+                let name = format!("macd_{}_{}_{}", fast, slow, signal);
+                vec![
+                    (
+                        name.clone(),
+                        points.iter().map(|p| p.map(|p| p.macd)).collect(),
+                    ),
+                    (
+                        format!("{}_signal", name),
+                        points.iter().map(|p| p.map(|p| p.signal)).collect(),
+                    ),
+                    (
+                        format!("{}_histogram", name),
+                        points.iter().map(|p| p.map(|p| p.histogram)).collect(),
+                    ),
+                ]
+            }
+            Indicator::Bollinger { period, deviations } => {
+                let bands = bollinger_bands(quotes, period, deviations);
+                let name = format!("bb_{}_{}", period, deviations);
+                vec![
+                    (
+                        format!("{}_lower", name),
+                        bands.iter().map(|b| b.map(|b| b.lower)).collect(),
+                    ),
+                    (
+                        format!("{}_middle", name),
+                        bands.iter().map(|b| b.map(|b| b.middle)).collect(),
+                    ),
+                    (
+                        format!("{}_upper", name),
+                        bands.iter().map(|b| b.map(|b| b.upper)).collect(),
+                    ),
+                ]
+            }
+            Indicator::Atr(period) => vec![(format!("atr_{}", period), atr(quotes, period))],
+        }
+    }
+}
+
+impl fmt::Display for Indicator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Indicator::Sma(period) => write!(f, "SMA({})", period),
+            Indicator::Ema(period) => write!(f, "EMA({})", period),
+            Indicator::Rsi(period) => write!(f, "RSI({})", period),
+            Indicator::Macd { fast, slow, signal } => {
+                write!(f, "MACD({}, {}, {})", fast, slow, signal)
+            }
+            Indicator::Bollinger { period, deviations } => {
+                write!(f, "Bollinger Bands({}, {})", period, deviations)
+            }
+            Indicator::Atr(period) => write!(f, "ATR({})", period),
+        }
+    }
+}
+
+impl FromStr for Indicator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let name = parts.next().unwrap_or_default().to_lowercase();
+        let params: Vec<f64> = parts
+            .map(|p| {
+                p.parse::<f64>()
+                    .map_err(|_| format!("Invalid parameter {} in {}", p, s))
+            })
+            .collect::<Result<_, _>>()?;
+        if params.iter().any(|p| *p <= 0.0) {
+            return Err(format!("Parameters of {} must be positive", s));
+        }
+        // Periods default to the usual values when they are left out
+        let period =
+            |i: usize, default: usize| params.get(i).map_or(default, |p| (*p as usize).max(1));
+        match name.as_str() {
+            "sma" => Ok(Indicator::Sma(period(0, 20))),
+            "ema" => Ok(Indicator::Ema(period(0, 20))),
+            "rsi" => Ok(Indicator::Rsi(period(0, 14))),
+            "macd" => Ok(Indicator::Macd {
+                fast: period(0, 12),
+                slow: period(1, 26),
+                signal: period(2, 9),
+            }),
+            "bb" => Ok(Indicator::Bollinger {
+                period: period(0, 20),
+                deviations: params.get(1).copied().unwrap_or(2.0),
+            }),
+            "atr" => Ok(Indicator::Atr(period(0, 14))),
+            _ => Err(format!(
+                "Unknown indicator {}, expected one of sma, ema, rsi, macd, bb, atr",
+                name
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote(high: f64, low: f64, close: f64) -> Quote {
+        Quote {
+            timestamp: 0,
+            open: close,
+            high,
+            low,
+            volume: 1000,
+            close,
+            adjclose: close,
+        }
+    }
+
+    fn closes(closes: &[f64]) -> Vec<Quote> {
+        closes
+            .iter()
+            .map(|close| quote(*close, *close, *close))
+            .collect()
+    }
+
+    fn assert_values(values: &[Option<f64>], expected: &[Option<f64>]) {
+        assert_eq!(values.len(), expected.len(), "{:?}", values);
+        for (value, expected) in values.iter().zip(expected) {
+            match (value, expected) {
+                (Some(value), Some(expected)) => {
+                    assert!((value - expected).abs() < 1e-9, "{:?}", values)
+                }
+                _ => assert_eq!(value, expected),
+            }
+        }
+    }
+
+    #[test]
+    fn sma_and_ema_of_known_closes() {
+        let quotes = closes(&[2.0, 4.0, 6.0, 8.0, 12.0]);
+        assert_values(
+            &sma(&quotes, 3),
+            &[None, None, Some(4.0), Some(6.0), Some(26.0 / 3.0)],
+        );
+        assert_values(
+            &ema(&quotes, 3),
+            &[None, None, Some(4.0), Some(6.0), Some(9.0)],
+        );
+    }
+
+    #[test]
+    fn rsi_of_known_closes() {
+        let quotes = closes(&[1.0, 2.0, 1.0, 3.0]);
+        assert_values(
+            &rsi(&quotes, 2),
+            &[None, None, Some(50.0), Some(100.0 - 100.0 / 6.0)],
+        );
+    }
+
+    #[test]
+    fn rsi_of_a_flat_series_is_neutral() {
+        let quotes = closes(&[5.0; 6]);
+        assert_values(
+            &rsi(&quotes, 3),
+            &[None, None, None, Some(50.0), Some(50.0), Some(50.0)],
+        );
+    }
+
+    #[test]
+    fn rsi_without_losses_is_100() {
+        let quotes = closes(&[1.0, 2.0, 3.0, 4.0]);
+        assert_values(&rsi(&quotes, 2), &[None, None, Some(100.0), Some(100.0)]);
+    }
+
+    #[test]
+    fn macd_of_known_closes() {
+        let quotes = closes(&[2.0, 4.0, 6.0, 8.0, 12.0]);
+        let points = macd(&quotes, 2, 3, 2);
+        assert!(points[..3].iter().all(Option::is_none));
+        let point = points[4].unwrap();
+        assert!((point.macd - 4.0 / 3.0).abs() < 1e-9);
+        assert!((point.signal - 11.0 / 9.0).abs() < 1e-9);
+        assert!((point.histogram - 1.0 / 9.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn bollinger_bands_of_known_closes() {
+        let quotes = closes(&[2.0, 4.0, 6.0]);
+        let bands = bollinger_bands(&quotes, 3, 2.0);
+        assert!(bands[..2].iter().all(Option::is_none));
+        let band = bands[2].unwrap();
+        let width = 2.0 * (8.0f64 / 3.0).sqrt();
+        assert!((band.middle - 4.0).abs() < 1e-9);
+        assert!((band.lower - (4.0 - width)).abs() < 1e-9);
+        assert!((band.upper - (4.0 + width)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn atr_includes_gaps_from_the_previous_close() {
+        let quotes = [
+            quote(3.0, 1.0, 2.0),
+            quote(5.0, 4.0, 4.5),
+            quote(5.0, 3.0, 4.0),
+        ];
+        assert_values(&atr(&quotes, 2), &[None, Some(2.5), Some(2.25)]);
+    }
+
+    #[test]
+    fn period_0_has_no_values() {
+        let quotes = closes(&[1.0, 2.0, 3.0]);
+        let none = [None; 3];
+        assert_values(&sma(&quotes, 0), &none);
+        assert_values(&ema(&quotes, 0), &none);
+        assert_values(&rsi(&quotes, 0), &none);
+        assert_values(&atr(&quotes, 0), &none);
+        assert!(bollinger_bands(&quotes, 0, 2.0).iter().all(Option::is_none));
+        assert!(macd(&quotes, 0, 0, 0).iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn period_longer_than_the_quotes_has_no_values() {
+        let quotes = closes(&[1.0, 2.0, 3.0]);
+        let none = [None; 3];
+        assert_values(&sma(&quotes, 4), &none);
+        assert_values(&ema(&quotes, 4), &none);
+        assert_values(&rsi(&quotes, 3), &none);
+        assert_values(&atr(&quotes, 4), &none);
+        assert!(bollinger_bands(&quotes, 4, 2.0).iter().all(Option::is_none));
+        assert!(macd(&quotes, 12, 26, 9).iter().all(Option::is_none));
+        assert_eq!(
+            Indicator::Rsi(14).latest(&quotes),
+            "not enough quotes".to_owned()
+        );
+    }
+
+    #[test]
+    fn indicators_are_parsed_with_default_periods() {
+        assert_eq!("sma".parse(), Ok(Indicator::Sma(20)));
+        assert_eq!("RSI:7".parse(), Ok(Indicator::Rsi(7)));
+        assert_eq!(
+            "macd::".parse::<Indicator>().map(|_| ()),
+            Err("Invalid parameter  in macd::".to_owned())
+        );
+        assert_eq!(
+            "bb:10:1.5".parse(),
+            Ok(Indicator::Bollinger {
+                period: 10,
+                deviations: 1.5
+            })
+        );
+        assert!("atr:0".parse::<Indicator>().is_err());
+        assert!("vwap".parse::<Indicator>().is_err());
+    }
+}
@@ -1,264 +1,949 @@
+mod alerts;
+mod api;
+mod backtest;
+mod benchmark;
+mod cache;
+mod charts;
+mod config;
+mod correlation;
+mod export;
+mod indicators;
+mod monitor;
+mod notify;
+mod plot;
+mod portfolio;
+mod provider;
+mod refresh;
+mod report;
+mod server;
+mod statistics;
+mod timeframe;
+mod volatility;
+
 use std::{
-    io::prelude::*,
-    net::{TcpListener, TcpStream},
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
     process,
+    time::Duration,
 };
-use time::{Duration, OffsetDateTime};
-use yahoo_finance_api as yahoo;
-use yahoo_finance_api::{Quote, YahooConnector};
+use time::OffsetDateTime;
+use yahoo_finance_api::Quote;
 
-use plotters::{drawing::IntoDrawingArea, prelude::*, style::RGBColor};
+use alerts::Alerts;
+use api::ApiError;
+use backtest::{write_trades, Backtest, BacktestResult, Strategy};
+use benchmark::BenchmarkComparison;
+use cache::CachedProvider;
+use config::{Config, DEFAULT_CONFIG_FILE};
+use correlation::CorrelationMatrix;
+use export::{Export, ExportFormat};
+use indicators::Indicator;
+use monitor::Monitor;
+use notify::{Notification, Notifier};
+use plot::{
+    parse_color, ChartFormat, ChartKind, ChartStyle, PriceScale, Theme, MAX_CHART_SIZE,
+    MIN_CHART_SIZE,
+};
+use portfolio::{Portfolio, PortfolioReport};
+use provider::{fetch_concurrently, write_quotes, FileProvider, QuoteProvider, YahooProvider};
+use refresh::Refresher;
+use report::StockReport;
+use server::Dashboard;
+use statistics::Statistics;
+use timeframe::{Interval, Range, Window};
+use volatility::{Volatility, VolatilityMeasure};
 
-use chrono::{Datelike, NaiveDate, TimeZone, Utc};
+use chrono::NaiveDate;
+use plotters::style::RGBColor;
 
-use clap::Parser;
+use clap::{
+    parser::ValueSource, ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum,
+};
 
 fn get_stock_prices(
     stock_name: &str,
     end_date: OffsetDateTime,
     start_date: OffsetDateTime,
-    provider: &YahooConnector,
-) -> Vec<Quote> {
-    // returns historic quotes with daily interval
-    let resp =
-        tokio_test::block_on(provider.get_quote_history(stock_name, start_date, end_date)).unwrap();
-    return resp.quotes().unwrap();
+    interval: Interval,
+    provider: &dyn QuoteProvider,
+) -> Result<Vec<Quote>, String> {
+    provider
+        .get_quote_history(stock_name, start_date, end_date, interval)
+        .map_err(|error| format!("Could not fetch quotes for {}: {}", stock_name, error))
 }
 
-fn plot_prices(
-    image_name: &str,
-    min_price: f64,
-    max_price: f64,
-    min_date: NaiveDate,
-    max_date: NaiveDate,
-    series: Vec<(NaiveDate, f64)>,
-    volatile_days: Vec<(NaiveDate, Quote)>,
-    stock_name: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Create a drawing area
-    let root = BitMapBackend::new(image_name, (800, 600)).into_drawing_area();
-    root.fill(&RGBColor(255, 255, 255))?;
-
-    // Configure a line chart
-    let mut chart = ChartBuilder::on(&root)
-        .caption(
-            stock_name.to_owned() + " Stock Prices",
-            ("Arial", 30).into_font(),
-        )
-        .x_label_area_size(40)
-        .y_label_area_size(40)
-        .build_cartesian_2d(min_date..max_date, min_price..max_price)?;
-
-    // Draw the line series
-    chart.configure_mesh().draw()?;
-    chart.draw_series(LineSeries::new(
-        series.iter().map(|(x, y)| (*x, *y)),
-        &RGBColor(255, 0, 0),
-    ))?;
-
-    // Draw the volatility data
-    chart
-        .draw_series(
-            volatile_days.iter().map(|(x, y)| {
-                ErrorBar::new_vertical(*x, y.low, y.close, y.high, BLUE.filled(), 10)
-            }),
-        )
-        .unwrap();
+/// How the charts of several stocks are laid out
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum Layout {
+    /// One chart per stock
+    Separate,
+    /// A single chart comparing the percent change of every stock
+    Overlay,
+}
+
+/// Monitor stock prices: fetch quotes, print stats, render charts or serve a dashboard
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
 
-    Ok(())
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Download the quotes of the stocks into the cache, or to CSV files
+    Fetch(FetchArgs),
+    /// Print the stats and analyses of the stocks, send their notifications and exit
+    Stats(StatsArgs),
+    /// Render the charts of the stocks to PNG, SVG or HTML files and exit
+    Plot(PlotArgs),
+    /// Serve the dashboard until Control+C is pressed
+    Serve(ServeArgs),
+    /// Write the bars of the stocks to a file and exit
+    Export(ExportArgs),
 }
 
-fn is_valid_stock(stock_name: &str, provider: &YahooConnector) -> bool {
-    match tokio_test::block_on(provider.get_latest_quotes(stock_name, "1d")) {
-        Ok(_) => true,
-        Err(_) => false,
-    }
+/// Which stocks are fetched, over which window and from where
+#[derive(clap::Args, Debug)]
+struct QuoteArgs {
+    /// Names of the stock tickers, ex. AAPL MSFT. Defaults to the watchlist or the tickers of --portfolio
+    stock_names: Vec<String>,
+    /// TOML file of watchlists and default options, overridden by the command line. Defaults to stock_market_monitor.toml if it exists
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// Watchlist of the config file to use instead of the tickers
+    #[arg(long, value_name = "NAME", conflicts_with = "stock_names")]
+    watchlist: Option<String>,
+    /// Time span to chart, ending today or at --end
+    #[arg(long, value_enum, default_value_t = Range::SixMonths)]
+    range: Range,
+    /// First date to chart, ex. 2024-01-31. Overrides --range
+    #[arg(long, value_name = "YYYY-MM-DD")]
+    start: Option<NaiveDate>,
+    /// Last date to chart, ex. 2024-06-30. Defaults to today
+    #[arg(long, value_name = "YYYY-MM-DD")]
+    end: Option<NaiveDate>,
+    /// Size of each price bar
+    #[arg(long, value_enum, default_value_t = Interval::OneDay)]
+    interval: Interval,
+    /// Indicators to chart and print, ex. sma:20,ema:50,bb:20:2,rsi:14,macd:12:26:9,atr:14
+    #[arg(long, value_delimiter = ',')]
+    indicators: Vec<Indicator>,
+    /// How volatile days are detected
+    #[arg(long, value_enum, default_value_t = VolatilityMeasure::Range)]
+    volatility: VolatilityMeasure,
+    /// Threshold above which a day is volatile. Defaults to 0.02 for range and return, 1.5 for atr and 2 for zscore
+    #[arg(long, value_name = "THRESHOLD")]
+    volatility_threshold: Option<f64>,
+    /// Number of previous bars used by the atr and zscore volatility measures
+    #[arg(long, value_name = "BARS", default_value_t = 20)]
+    volatility_window: usize,
+    /// Read quotes from a CSV/JSON file, or a directory of <TICKER>.csv/.json files, instead of Yahoo Finance
+    #[arg(long, value_name = "PATH")]
+    data: Option<PathBuf>,
+    /// Directory where fetched quotes are cached between runs
+    #[arg(long, value_name = "DIR", default_value = "quote_cache")]
+    cache_dir: PathBuf,
+    /// Serve quotes purely from the cache without contacting Yahoo Finance
+    #[arg(long, conflicts_with = "data")]
+    offline: bool,
 }
 
-fn handle_connection(mut stream: TcpStream) -> std::io::Result<()> {
-    let mut buffer = [0; 1024];
-    stream.read(&mut buffer)?;
-
-    // Handle the recieved request
-    if buffer.starts_with(b"GET / HTTP/1.1\r\n") {
-        // Serve the html file
-        let status_line = "HTTP/1.1 200 OK";
-        let contents = std::fs::read_to_string("src/plots.html").unwrap();
-        let length = contents.len();
-
-        stream.write_all(
-            format!("{status_line}\r\nContent-Length: {length}\r\n\r\n{contents}").as_bytes(),
-        )?;
-        Ok(())
-    } else if buffer.starts_with(b"GET /stock_image.png HTTP/1.1\r\n") {
-        // Serve the plot without volatility data
-        write_file_to_stream("stock_prices.png", stream)
-    } else if buffer.starts_with(b"GET /volatile_image.png HTTP/1.1\r\n") {
-        // Serve the plot with volatility data
-        write_file_to_stream("volatile_stock_prices.png", stream)
-    } else {
-        // Invalid request case
-        println!("Invalid Request");
-        Ok(())
-    }
+/// Analyses run on top of the stats and charts of the stocks
+#[derive(clap::Args, Debug)]
+struct AnalysisArgs {
+    /// CSV file of positions with the header ticker,quantity,cost_basis,purchase_date
+    #[arg(long, value_name = "FILE")]
+    portfolio: Option<PathBuf>,
+    /// Strategy backtested over the selected window, ex. sma-crossover:20:50 or volatile-dip:5
+    #[arg(long, value_name = "STRATEGY")]
+    backtest: Option<Strategy>,
+    /// Equity the backtest starts with
+    #[arg(long, value_name = "DOLLARS", default_value_t = 10000.0)]
+    capital: f64,
+    /// Fee paid on every backtested buy and sell
+    #[arg(long, value_name = "DOLLARS", default_value_t = 0.0)]
+    commission: f64,
+    /// Percentage of the price lost on every backtested fill, ex. 0.05
+    #[arg(long, value_name = "PERCENT", default_value_t = 0.0)]
+    slippage: f64,
+    /// Ticker the stocks are compared against, ex. SPY
+    #[arg(long, value_name = "TICKER")]
+    benchmark: Option<String>,
 }
 
-fn write_file_to_stream(name: &str, mut stream: TcpStream) -> std::io::Result<()> {
-    let mut file = std::fs::File::open(name)?;
-    let mut contents = Vec::new();
-    file.read_to_end(&mut contents)?;
-    let response = format!(
-        "HTTP/1.1 200 OK\r\nContent-Type: stocks_image/png\r\nContent-Length: {}\r\n\r\n",
-        contents.len()
-    );
-    let mut response = response.into_bytes();
+/// Where alerts and notifications are sent
+#[derive(clap::Args, Debug)]
+struct NotifyArgs {
+    /// JSON file of alert rules, checked at startup and on every refresh
+    #[arg(long, value_name = "FILE")]
+    alerts: Option<PathBuf>,
+    /// JSON file of the sinks the stats summaries and volatile bars are sent to
+    #[arg(long, value_name = "FILE")]
+    notify: Option<PathBuf>,
+}
 
-    response.extend(contents);
-    stream.write_all(&response)
+/// Which charts are drawn
+#[derive(clap::Args, Debug)]
+struct ChartArgs {
+    /// Draw one chart per stock or overlay all stocks on one comparison chart
+    #[arg(long, value_enum, default_value_t = Layout::Separate)]
+    layout: Layout,
+    /// How the prices are drawn, the served page can toggle to the other one
+    #[arg(long, value_enum, default_value_t = ChartKind::Line)]
+    chart: ChartKind,
+    /// Width of the charts in pixels, between 200 and 10000. Defaults to 800
+    #[arg(
+        long,
+        value_name = "PIXELS",
+        value_parser = clap::value_parser!(u32).range(MIN_CHART_SIZE as i64..=MAX_CHART_SIZE as i64)
+    )]
+    width: Option<u32>,
+    /// Height of the charts in pixels, between 200 and 10000. Defaults to 600
+    #[arg(
+        long,
+        value_name = "PIXELS",
+        value_parser = clap::value_parser!(u32).range(MIN_CHART_SIZE as i64..=MAX_CHART_SIZE as i64)
+    )]
+    height: Option<u32>,
+    /// Colors of the chart background, text and gridlines. Defaults to light
+    #[arg(long, value_enum)]
+    theme: Option<Theme>,
+    /// Font family of the chart titles and labels. Defaults to Arial
+    #[arg(long)]
+    font: Option<String>,
+    /// Font size of the chart titles in pixels. Defaults to 30
+    #[arg(long, value_name = "PIXELS")]
+    caption_size: Option<u32>,
+    /// Color of the closing price line as #rrggbb. Defaults to #ff0000
+    #[arg(long, value_name = "COLOR", value_parser = parse_color)]
+    price_color: Option<RGBColor>,
+    /// Color of the volatile bars as #rrggbb. Defaults to #0000ff
+    #[arg(long, value_name = "COLOR", value_parser = parse_color)]
+    volatile_color: Option<RGBColor>,
+    /// Color of the candles and volume bars that closed up as #rrggbb. Defaults to #009600
+    #[arg(long, value_name = "COLOR", value_parser = parse_color)]
+    up_color: Option<RGBColor>,
+    /// Color of the candles and volume bars that closed down as #rrggbb. Defaults to #c80000
+    #[arg(long, value_name = "COLOR", value_parser = parse_color)]
+    down_color: Option<RGBColor>,
+    /// Spacing of the prices on the vertical axis. Defaults to linear
+    #[arg(long, value_enum)]
+    price_scale: Option<PriceScale>,
+    /// Room left above and below the prices in percent of their range. Defaults to 5
+    #[arg(long, value_name = "PERCENT")]
+    price_padding: Option<f64>,
+    /// Leave out the gridlines behind the series
+    #[arg(long)]
+    no_gridlines: bool,
 }
 
-/// Generate plots from inputted stock names
-#[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
-struct Args {
-    /// Name of the stock ticker, ex. AAPL
-    stock_name: String,
+#[derive(clap::Args, Debug)]
+struct FetchArgs {
+    #[command(flatten)]
+    quotes: QuoteArgs,
+    /// Directory the quotes are also written to as <TICKER>.csv, readable with --data
+    #[arg(long, value_name = "DIR")]
+    output: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+struct StatsArgs {
+    #[command(flatten)]
+    quotes: QuoteArgs,
+    #[command(flatten)]
+    analysis: AnalysisArgs,
+    #[command(flatten)]
+    notify: NotifyArgs,
+    /// CSV file the backtested trades are written to
+    #[arg(long, value_name = "FILE", requires = "backtest")]
+    trades: Option<PathBuf>,
+    /// CSV file the correlation matrix of the stocks' returns is written to
+    #[arg(long, value_name = "FILE")]
+    correlation: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+struct PlotArgs {
+    #[command(flatten)]
+    quotes: QuoteArgs,
+    #[command(flatten)]
+    analysis: AnalysisArgs,
+    #[command(flatten)]
+    charts: ChartArgs,
+    /// Directory the charts are written to, one file per chart
+    #[arg(long, value_name = "DIR", default_value = ".")]
+    output_dir: PathBuf,
+    /// File format of the charts
+    #[arg(long, value_enum, default_value_t = ChartFormat::Png)]
+    format: ChartFormat,
+    /// Leave the volatile bars unmarked on the price charts
+    #[arg(long)]
+    hide_volatile: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct ServeArgs {
+    #[command(flatten)]
+    quotes: QuoteArgs,
+    #[command(flatten)]
+    analysis: AnalysisArgs,
+    #[command(flatten)]
+    charts: ChartArgs,
+    #[command(flatten)]
+    notify: NotifyArgs,
+    /// Local address to host the fancy plot on
+    #[arg(long, default_value = "127.0.0.1")]
+    address: String,
     /// Local port number to host the fancy plot
-    port_number: Option<String>,
+    #[arg(short, long, default_value_t = 4567)]
+    port: u16,
+    /// Seconds between polls of the latest quotes while the server runs, 0 to never refresh
+    #[arg(long, value_name = "SECONDS", default_value_t = 60)]
+    refresh: u64,
+    /// Keep polling while the US stock market is closed
+    #[arg(long)]
+    refresh_outside_market_hours: bool,
 }
 
-fn main() {
-    let args = Args::parse();
-    let stock_name: &str = &args.stock_name;
-    let port_number: u32 = match &args.port_number {
-        Some(p) => p.parse().unwrap_or(4567),
-        None => 4567,
+#[derive(clap::Args, Debug)]
+struct ExportArgs {
+    #[command(flatten)]
+    quotes: QuoteArgs,
+    /// File the bars are written to
+    #[arg(short, long, value_name = "FILE")]
+    output: PathBuf,
+    /// Format of the file, by default guessed from its extension and otherwise CSV
+    #[arg(long, value_enum)]
+    format: Option<ExportFormat>,
+}
+
+/// The options completed by the config file, and where the quotes of the stocks come from
+struct Session {
+    config: Config,
+    monitor: Monitor,
+    window: Window,
+    dates: (OffsetDateTime, OffsetDateTime),
+    portfolio: Option<Portfolio>,
+}
+
+/// Reads the explicitly given config file, or the default one if there is one
+fn load_config(quotes: &QuoteArgs) -> Option<Config> {
+    let path = quotes.config.clone().or_else(|| {
+        let path = PathBuf::from(DEFAULT_CONFIG_FILE);
+        path.exists().then_some(path)
+    })?;
+    match Config::load(&path) {
+        Ok(config) => Some(config),
+        Err(error) => {
+            eprintln!(
+                "Error: Could not read the config file {}: {}",
+                path.display(),
+                error
+            );
+            process::exit(1);
+        }
+    }
+}
+
+/// Fills the options not given on the command line from the config file
+fn apply_config(quotes: &mut QuoteArgs, config: &Config, matches: &ArgMatches) {
+    let from_command_line = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+    if quotes.stock_names.is_empty() {
+        if let Some(name) = quotes.watchlist.as_ref().or(config.watchlist.as_ref()) {
+            match config.watchlist(name) {
+                Ok(stock_names) => quotes.stock_names = stock_names.to_vec(),
+                Err(error) => {
+                    eprintln!("Error: {}.", error);
+                    process::exit(1);
+                }
+            }
+        }
+    }
+    if let (Some(range), false) = (config.range, from_command_line("range")) {
+        quotes.range = range;
+    }
+    if let (Some(interval), false) = (config.interval, from_command_line("interval")) {
+        quotes.interval = interval;
+    }
+    if let (Some(volatility), false) = (config.volatility, from_command_line("volatility")) {
+        quotes.volatility = volatility;
+    }
+    if quotes.volatility_threshold.is_none() {
+        quotes.volatility_threshold = config.volatility_threshold;
+    }
+    if let (Some(window), false) = (
+        config.volatility_window,
+        from_command_line("volatility_window"),
+    ) {
+        quotes.volatility_window = window;
+    }
+    if let (Some(cache_dir), false) = (&config.cache_dir, from_command_line("cache_dir")) {
+        quotes.cache_dir = cache_dir.clone();
+    }
+}
+
+/// Completes the options with the config file, then checks the stocks and the selected window.
+/// The portfolio's tickers are used when no stocks are given, and the extra tickers are only
+/// checked.
+fn start_session(
+    quotes: &mut QuoteArgs,
+    matches: &ArgMatches,
+    portfolio: Option<&Path>,
+    extra_tickers: &[String],
+) -> Session {
+    let config = load_config(quotes);
+    if quotes.watchlist.is_some() && config.is_none() {
+        eprintln!("Error: --watchlist needs a config file with watchlists.");
+        process::exit(1);
+    }
+    let config = config.unwrap_or_default();
+    apply_config(quotes, &config, matches);
+
+    let portfolio = portfolio.map(|path| match Portfolio::load(path) {
+        Ok(portfolio) => portfolio,
+        Err(error) => {
+            eprintln!(
+                "Error: Could not read the portfolio file {}: {}",
+                path.display(),
+                error
+            );
+            process::exit(1);
+        }
+    });
+    if let Some(portfolio) = &portfolio {
+        if quotes.stock_names.is_empty() {
+            quotes.stock_names = portfolio.tickers();
+        }
+    }
+    if quotes.stock_names.is_empty() {
+        eprintln!(
+            "Error: No stock symbols given, pass some tickers, a --watchlist or a --portfolio."
+        );
+        process::exit(1);
+    }
+
+    let provider: Box<dyn QuoteProvider> = match &quotes.data {
+        Some(path) => Box::new(FileProvider::new(path)),
+        None => match YahooProvider::new() {
+            Ok(p) => Box::new(CachedProvider::new(
+                Box::new(p),
+                &quotes.cache_dir,
+                quotes.offline,
+            )),
+            Err(error) => {
+                eprintln!("Error: Could not connect to Yahoo Finance: {}", error);
+                process::exit(1);
+            }
+        },
+    };
+    let monitor = Monitor {
+        provider,
+        interval: quotes.interval,
+        volatility: Volatility::new(
+            quotes.volatility,
+            quotes.volatility_threshold,
+            quotes.volatility_window,
+        ),
+        indicators: quotes.indicators.clone(),
+        anchor_to_data: quotes.data.is_some(),
     };
 
-    let provider = yahoo::YahooConnector::new();
+    // Check if stock symbols are valid
+    let portfolio_tickers = portfolio
+        .as_ref()
+        .map(Portfolio::tickers)
+        .unwrap_or_default();
+    for stock_name in quotes
+        .stock_names
+        .iter()
+        .chain(&portfolio_tickers)
+        .chain(extra_tickers)
+    {
+        if !monitor.provider.is_valid_stock(stock_name) {
+            eprintln!("Error: The stock symbol {} is not valid.", stock_name);
+            process::exit(1);
+        }
+    }
 
-    // Check if stock symbol is valid
-    if !is_valid_stock(stock_name, &provider) {
-        eprintln!("Error: The stock symbol {} is not valid.", stock_name);
+    // Get today's date and the start of the selected range
+    let window = Window {
+        range: quotes.range,
+        start: quotes.start,
+        end: quotes.end,
+    };
+    let dates = window
+        .resolve(monitor.today(&quotes.stock_names))
+        .unwrap_or_else(|error| {
+            eprintln!("Error: {}.", error);
+            process::exit(1);
+        });
+    if dates.0 >= dates.1 {
+        eprintln!("Error: The start date must be before the end date.");
         process::exit(1);
     }
 
-    // Get today's date and six months prior date
-    let today = OffsetDateTime::now_utc();
-    let six_months_ago = today - Duration::days(30 * 6);
+    Session {
+        config,
+        monitor,
+        window,
+        dates,
+        portfolio,
+    }
+}
+
+/// Fetches the reports of the stocks concurrently, in the order of the names
+fn fetch_reports(session: &Session, stock_names: &[String]) -> Vec<StockReport> {
+    let provider = session.monitor.provider.as_ref();
+    let interval = session.monitor.interval;
+    let volatility = &session.monitor.volatility;
+    let (start_date, end_date) = session.dates;
+    fetch_concurrently(stock_names, |stock_name| {
+        let quotes = get_stock_prices(stock_name, end_date, start_date, interval, provider)?;
+        StockReport::new(stock_name, &quotes, interval, volatility)
+            .ok_or_else(|| format!("No quotes found for {} in the selected range.", stock_name))
+    })
+    .unwrap_or_else(|error| {
+        eprintln!("Error: {}", error);
+        process::exit(1);
+    })
+}
 
-    // Get daily stock quotes from yahoo finance
-    let quotes: Vec<Quote> = get_stock_prices(stock_name, today, six_months_ago, &provider);
+/// Reads the --alerts file, or else the alert rules of the config file
+fn load_alerts(path: Option<&Path>, config: &mut Config) -> Option<Alerts> {
+    match (path, config.alerts.take()) {
+        (Some(path), _) => Some(Alerts::load(path).unwrap_or_else(|error| {
+            eprintln!(
+                "Error: Could not read the alerts file {}: {}",
+                path.display(),
+                error
+            );
+            process::exit(1);
+        })),
+        (None, Some(alert_config)) => Some(Alerts::new(alert_config).unwrap_or_else(|error| {
+            eprintln!(
+                "Error: Could not set up the alerts of the config file: {}",
+                error
+            );
+            process::exit(1);
+        })),
+        (None, None) => None,
+    }
+}
 
-    // Convert date format to Naive Date
-    let min_date: NaiveDate = match NaiveDate::from_ymd_opt(
-        six_months_ago.year(),
-        six_months_ago.month() as u32,
-        six_months_ago.day().into(),
-    ) {
-        Some(date) => date,
-        None => {
-            panic!("Invalid date");
+fn load_notifier(path: Option<&Path>) -> Option<Notifier> {
+    path.map(|path| match Notifier::load(path) {
+        Ok(notifier) => notifier,
+        Err(error) => {
+            eprintln!(
+                "Error: Could not read the notifications file {}: {}",
+                path.display(),
+                error
+            );
+            process::exit(1);
         }
+    })
+}
+
+fn backtest(analysis: &AnalysisArgs) -> Option<Backtest> {
+    analysis.backtest.map(|strategy| Backtest {
+        strategy,
+        capital: analysis.capital,
+        commission: analysis.commission,
+        slippage: analysis.slippage,
+    })
+}
+
+/// Builds the dashboard rendering the charts of the selected layout and analyses, without
+/// alerts, notifications or refreshes
+fn dashboard(
+    session: Session,
+    stock_names: &[String],
+    analysis: &AnalysisArgs,
+    charts: &ChartArgs,
+) -> Dashboard {
+    let mut chart_names = match charts.layout {
+        Layout::Separate => stock_names.to_vec(),
+        Layout::Overlay => vec!["comparison".to_owned()],
     };
-    let max_date: NaiveDate =
-        match NaiveDate::from_ymd_opt(today.year(), today.month() as u32, today.day().into()) {
-            Some(date) => date,
-            None => {
-                panic!("Invalid date");
-            }
-        };
-
-    //This iterator is created to help derive the daily data, volatile days and min/max close
-    let date_quote_pairs = quotes.iter().map(|quote| {
-        let datetime_utc = Utc.timestamp_opt(quote.timestamp as i64, 0).unwrap();
-        let quote_date: NaiveDate = match NaiveDate::from_ymd_opt(
-            datetime_utc.year(),
-            datetime_utc.month() as u32,
-            datetime_utc.day().into(),
-        ) {
-            Some(date) => date,
-            None => {
-                panic!("Invalid date");
-            }
-        };
-        (quote_date, quote)
+    if stock_names.len() > 1 {
+        chart_names.push("correlation".to_owned());
+    }
+    if analysis.backtest.is_some() {
+        for stock_name in stock_names {
+            chart_names.push(format!("equity/{}", stock_name));
+        }
+    }
+    if analysis.benchmark.is_some() {
+        for stock_name in stock_names {
+            chart_names.push(format!("benchmark/{}", stock_name));
+        }
+    }
+    if session.portfolio.is_some() {
+        chart_names.push("portfolio".to_owned());
+    }
+
+    Dashboard {
+        chart_names,
+        chart_kind: charts.chart,
+        style: chart_style(charts, session.config.chart),
+        stock_names: stock_names.to_vec(),
+        monitor: session.monitor,
+        window: session.window,
+        alerts: None,
+        notifier: None,
+        backtest: backtest(analysis),
+        benchmark: analysis.benchmark.clone(),
+        portfolio: session.portfolio,
+        refresher: Refresher::new(None, true),
+    }
+}
+
+/// The chart style of the config file with the chart options given on the command line
+fn chart_style(charts: &ChartArgs, mut style: ChartStyle) -> ChartStyle {
+    style.width = charts.width.unwrap_or(style.width);
+    style.height = charts.height.unwrap_or(style.height);
+    style.theme = charts.theme.unwrap_or(style.theme);
+    style.font = charts.font.clone().unwrap_or(style.font);
+    style.caption_size = charts.caption_size.unwrap_or(style.caption_size);
+    style.price_color = charts.price_color.unwrap_or(style.price_color);
+    style.volatile_color = charts.volatile_color.unwrap_or(style.volatile_color);
+    style.up_color = charts.up_color.unwrap_or(style.up_color);
+    style.down_color = charts.down_color.unwrap_or(style.down_color);
+    style.price_scale = charts.price_scale.unwrap_or(style.price_scale);
+    style.price_padding = charts.price_padding.unwrap_or(style.price_padding);
+    style.gridlines &= !charts.no_gridlines;
+    style
+}
+
+/// Downloads the quotes of every stock, filling the cache, and optionally writes them to files
+fn fetch(mut args: FetchArgs, matches: &ArgMatches) {
+    let session = start_session(&mut args.quotes, matches, None, &[]);
+    let provider = session.monitor.provider.as_ref();
+    let interval = session.monitor.interval;
+    let (start_date, end_date) = session.dates;
+    if let Some(dir) = &args.output {
+        if let Err(error) = fs::create_dir_all(dir) {
+            eprintln!("Error: Could not create {}: {}", dir.display(), error);
+            process::exit(1);
+        }
+    }
+
+    let histories = fetch_concurrently(&args.quotes.stock_names, |stock_name| {
+        get_stock_prices(stock_name, end_date, start_date, interval, provider)
+    })
+    .unwrap_or_else(|error| {
+        eprintln!("Error: {}", error);
+        process::exit(1);
     });
+    for (stock_name, quotes) in args.quotes.stock_names.iter().zip(histories) {
+        println!("Fetched {} quotes of {}", quotes.len(), stock_name);
+        if let Some(dir) = &args.output {
+            let path = dir.join(format!("{}.csv", stock_name));
+            if let Err(error) = write_quotes(&path, &quotes) {
+                eprintln!("Error: Could not write {}: {}", path.display(), error);
+                process::exit(1);
+            }
+        }
+    }
+}
 
-    // Derive daily closing prices
-    let series: Vec<(NaiveDate, f64)> = date_quote_pairs
-        .clone()
-        .map(|(quote_date, quote)| (quote_date, quote.close))
-        .collect();
+/// Prints the stats and analyses of every stock, sends the notifications and checks the alerts
+fn stats(mut args: StatsArgs, matches: &ArgMatches) {
+    let benchmark: Vec<String> = args.analysis.benchmark.iter().cloned().collect();
+    let mut session = start_session(
+        &mut args.quotes,
+        matches,
+        args.analysis.portfolio.as_deref(),
+        &benchmark,
+    );
+    let alerts = load_alerts(args.notify.alerts.as_deref(), &mut session.config);
+    let notifier = load_notifier(args.notify.notify.as_deref());
+    let monitor = &session.monitor;
+    let indicators = &args.quotes.indicators;
 
-    // Derive volatility data
-    let volatile_days: Vec<(NaiveDate, Quote)> = date_quote_pairs
-        .clone()
-        .filter(|(_, quote)| ((quote.high - quote.low) / quote.close).abs() > 0.02)
-        .map(|(quote_date, quote)| (quote_date, quote.clone()))
+    // Get stock quotes for every stock and the benchmark concurrently
+    let stock_names: Vec<String> = args
+        .quotes
+        .stock_names
+        .iter()
+        .chain(&benchmark)
+        .cloned()
         .collect();
+    let mut reports = fetch_reports(&session, &stock_names);
+    let benchmark_report = args.analysis.benchmark.as_ref().and_then(|_| reports.pop());
 
-    // Derive min closing prices over 6 months
-    let (min_quote_date, min_quote_price) = date_quote_pairs
-        .clone()
-        .min_by(|(_, quote1), (_, quote2)| quote1.close.partial_cmp(&quote2.close).unwrap())
-        .map(|(date, quote)| (date, quote.close))
-        .unwrap();
+    // Ouput the min/max data, the risk and return statistics and the benchmark comparison
+    for report in &reports {
+        report.print_stats(indicators);
+        if let Some(statistics) = Statistics::new(report) {
+            statistics.print();
+        }
+        if let Some(benchmark_report) = &benchmark_report {
+            match BenchmarkComparison::new(report, benchmark_report) {
+                Some(comparison) => comparison.print(),
+                None => eprintln!(
+                    "Could not compare {} with {}: they share fewer than 3 bars",
+                    report.stock_name, benchmark_report.stock_name
+                ),
+            }
+        }
+    }
 
-    // Derive max closing prices over 6 months
-    let (max_quote_date, max_quote_price) = date_quote_pairs
-        .max_by(|(_, quote1), (_, quote2)| quote1.close.partial_cmp(&quote2.close).unwrap())
-        .map(|(date, quote)| (date, quote.close))
-        .unwrap();
+    // Correlate the returns of every pair of stocks
+    if args.correlation.is_some() && reports.len() < 2 {
+        eprintln!("Error: At least 2 stock symbols are needed for the correlation matrix.");
+        process::exit(1);
+    }
+    if reports.len() > 1 {
+        let matrix = CorrelationMatrix::new(&reports);
+        matrix.print();
+        if let Some(path) = &args.correlation {
+            if let Err(error) = matrix.write_csv(path) {
+                eprintln!(
+                    "Error: Could not write the correlation matrix to {}: {}",
+                    path.display(),
+                    error
+                );
+                process::exit(1);
+            }
+        }
+    }
 
-    // Ouput the min/max data
-    println!(
-        "{} Stats:\nMax Closing Price: ${:.2} on {}\nMin Closing Price: ${:.2} on {}",
-        stock_name, max_quote_price, max_quote_date, min_quote_price, min_quote_date
-    );
+    if let Some(notifier) = &notifier {
+        for report in &reports {
+            notifier.notify(&Notification::summary(report, indicators));
+            if let Some(volatile) = Notification::volatile(report, &monitor.volatility) {
+                notifier.notify(&volatile);
+            }
+        }
+    }
+    if let Some(alerts) = &alerts {
+        alerts.check(monitor);
+    }
+
+    // Backtest the strategy over the quotes of every stock
+    if let Some(backtest) = backtest(&args.analysis) {
+        let results: Vec<BacktestResult> = reports
+            .iter()
+            .map(|report| backtest.run(report, &monitor.volatility))
+            .collect();
+        for result in &results {
+            result.print();
+        }
+        if let Some(path) = &args.trades {
+            if let Err(error) = write_trades(path, &results) {
+                eprintln!(
+                    "Error: Could not write the trades to {}: {}",
+                    path.display(),
+                    error
+                );
+                process::exit(1);
+            }
+        }
+    }
 
-    // Create a plot with volatility data and one without the volatility data
-    let _ = plot_prices(
-        "volatile_stock_prices.png",
-        min_quote_price,
-        max_quote_price,
-        min_date,
-        max_date,
-        series.clone(),
-        volatile_days,
-        stock_name,
+    // Value the portfolio with the daily quotes since its first purchase
+    if let Some(portfolio) = &session.portfolio {
+        let provider = monitor.provider.as_ref();
+        let portfolio_tickers = portfolio.tickers();
+        let start_date = portfolio.start_date;
+        let today = monitor.today(&portfolio_tickers);
+        let histories: HashMap<String, Vec<Quote>> =
+            fetch_concurrently(&portfolio_tickers, |ticker| {
+                get_stock_prices(ticker, today, start_date, Interval::OneDay, provider)
+                    .map(|quotes| (ticker.to_owned(), quotes))
+            })
+            .unwrap_or_else(|error| {
+                eprintln!("Error: {}", error);
+                process::exit(1);
+            })
+            .into_iter()
+            .collect();
+        match PortfolioReport::new(portfolio, &histories) {
+            Some(report) => report.print(),
+            None => {
+                eprintln!("Error: No quotes found for every stock of the portfolio.");
+                process::exit(1);
+            }
+        }
+    }
+}
+
+/// Renders every chart the dashboard would show to a file of the selected format
+fn plot(mut args: PlotArgs, matches: &ArgMatches) {
+    let benchmark: Vec<String> = args.analysis.benchmark.iter().cloned().collect();
+    let session = start_session(
+        &mut args.quotes,
+        matches,
+        args.analysis.portfolio.as_deref(),
+        &benchmark,
     );
-    let _ = plot_prices(
-        "stock_prices.png",
-        min_quote_price,
-        max_quote_price,
-        min_date,
-        max_date,
-        series,
-        Vec::new(),
-        stock_name,
+    let dashboard = dashboard(
+        session,
+        &args.quotes.stock_names,
+        &args.analysis,
+        &args.charts,
     );
+    if let Err(error) = fs::create_dir_all(&args.output_dir) {
+        eprintln!(
+            "Error: Could not create {}: {}",
+            args.output_dir.display(),
+            error
+        );
+        process::exit(1);
+    }
 
-    // Serve the generated plots on local host
-    let address = format!("127.0.0.1:{}", port_number);
-    let listener = match TcpListener::bind(address) {
-        Ok(l) => l,
-        Err(_) => {
-            panic! {"Problem hosting backend. Please ensure a valid port number is specified."}
+    // Render the same charts as the dashboard, named after their page in the file names
+    let write = |name: &str, chart: Result<Vec<u8>, ApiError>| {
+        let path = args
+            .output_dir
+            .join(format!("{}.{}", name, args.format.extension()));
+        let written = chart
+            .map_err(|error| error.to_string())
+            .and_then(|chart| fs::write(&path, chart).map_err(|error| error.to_string()));
+        match written {
+            Ok(()) => println!("Wrote {}", path.display()),
+            Err(error) => {
+                eprintln!("Error: Could not plot {}: {}", name, error);
+                process::exit(1);
+            }
         }
     };
+    let stock_names = &args.quotes.stock_names;
+    let (window, interval, format) = (dashboard.window, dashboard.monitor.interval, args.format);
+    match args.charts.layout {
+        Layout::Separate => {
+            for stock_name in stock_names {
+                let chart = charts::render_prices(
+                    stock_name,
+                    window,
+                    interval,
+                    args.charts.chart,
+                    !args.hide_volatile,
+                    format,
+                    &dashboard,
+                );
+                write(stock_name, chart);
+            }
+        }
+        Layout::Overlay => write(
+            "comparison",
+            charts::render_comparison(stock_names, window, interval, format, &dashboard),
+        ),
+    }
+    if stock_names.len() > 1 {
+        write(
+            "correlation",
+            charts::render_correlation(stock_names, window, interval, format, &dashboard),
+        );
+    }
+    if dashboard.backtest.is_some() {
+        for stock_name in stock_names {
+            write(
+                &format!("equity_{}", stock_name),
+                charts::render_equity(stock_name, window, interval, format, &dashboard),
+            );
+        }
+    }
+    if dashboard.benchmark.is_some() {
+        for stock_name in stock_names {
+            write(
+                &format!("benchmark_{}", stock_name),
+                charts::render_benchmark(stock_name, window, interval, format, &dashboard),
+            );
+        }
+    }
+    if dashboard.portfolio.is_some() {
+        write("portfolio", charts::render_portfolio(format, &dashboard));
+    }
+}
+
+/// Serves the dashboard, checking the alerts and pushing updates on every refresh
+fn serve(mut args: ServeArgs, matches: &ArgMatches) {
+    let benchmark: Vec<String> = args.analysis.benchmark.iter().cloned().collect();
+    let mut session = start_session(
+        &mut args.quotes,
+        matches,
+        args.analysis.portfolio.as_deref(),
+        &benchmark,
+    );
+    if let (Some(address), false) = (
+        &session.config.server.address,
+        matches.value_source("address") == Some(ValueSource::CommandLine),
+    ) {
+        args.address = address.clone();
+    }
+    if let (Some(port), false) = (
+        session.config.server.port,
+        matches.value_source("port") == Some(ValueSource::CommandLine),
+    ) {
+        args.port = port;
+    }
+    let alerts = load_alerts(args.notify.alerts.as_deref(), &mut session.config);
+    let notifier = load_notifier(args.notify.notify.as_deref());
+
+    // Serve the plots on local host
+    let address = format!("{}:{}", args.address, args.port);
+    let dashboard = Dashboard {
+        alerts,
+        notifier,
+        refresher: Refresher::new(
+            (args.refresh > 0).then(|| Duration::from_secs(args.refresh)),
+            !args.refresh_outside_market_hours,
+        ),
+        ..dashboard(
+            session,
+            &args.quotes.stock_names,
+            &args.analysis,
+            &args.charts,
+        )
+    };
+    if let Err(error) = server::serve(&address, dashboard) {
+        eprintln!(
+            "Error: Problem hosting backend: {}. Please ensure a valid port number is specified.",
+            error
+        );
+        process::exit(1);
+    }
+}
+
+/// Writes the bars of every stock to one file
+fn export(mut args: ExportArgs, matches: &ArgMatches) {
+    let session = start_session(&mut args.quotes, matches, None, &[]);
+    let reports = fetch_reports(&session, &args.quotes.stock_names);
+    let format = args
+        .format
+        .or_else(|| ExportFormat::from_path(&args.output))
+        .unwrap_or(ExportFormat::Csv);
+    let export = Export::new(&reports, &args.quotes.indicators);
+    if let Err(error) = export.write(&args.output, format) {
+        eprintln!(
+            "Error: Could not export to {}: {}",
+            args.output.display(),
+            error
+        );
+        process::exit(1);
+    }
     println!(
-        "Open http://127.0.0.1:{} in your browser. \nPress Control + C to stop.",
-        port_number
+        "Exported {} bars of {} stocks to {}",
+        export.bar_count(),
+        reports.len(),
+        args.output.display()
     );
-    listener
-        .incoming()
-        .into_iter()
-        .for_each(|stream: Result<TcpStream, std::io::Error>| {
-            let stream = stream.unwrap();
-            let _ = match handle_connection(stream) {
-                Ok(()) => true,
-                Err(error) => panic! {"Problem Handling Request: {:?}", error},
-            };
-        });
+}
+
+fn main() {
+    let matches = Args::command().get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|error| error.exit());
+    // The options of the subcommand, to tell which ones the config file may override
+    let (_, matches) = matches.subcommand().unwrap();
+    match args.command {
+        Command::Fetch(args) => fetch(args, matches),
+        Command::Stats(args) => stats(args, matches),
+        Command::Plot(args) => plot(args, matches),
+        Command::Serve(args) => serve(args, matches),
+        Command::Export(args) => export(args, matches),
+    }
 }
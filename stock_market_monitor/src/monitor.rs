@@ -0,0 +1,52 @@
+use time::OffsetDateTime;
+
+use crate::{
+    indicators::Indicator,
+    provider::{ProviderError, QuoteProvider},
+    report::StockReport,
+    timeframe::Interval,
+    volatility::Volatility,
+};
+
+/// The quote provider and analysis settings, shared by the command line and the server
+pub struct Monitor {
+    pub provider: Box<dyn QuoteProvider>,
+    pub interval: Interval,
+    pub volatility: Volatility,
+    pub indicators: Vec<Indicator>,
+    /// Whether "today" is the latest quote of the data rather than the current time,
+    /// so that runs over the same local data files are reproducible
+    pub anchor_to_data: bool,
+}
+
+impl Monitor {
+    /// Returns the end of ranges that are relative to today
+    pub fn today(&self, stock_names: &[String]) -> OffsetDateTime {
+        if !self.anchor_to_data {
+            return OffsetDateTime::now_utc();
+        }
+        stock_names
+            .iter()
+            .filter_map(|stock_name| self.provider.get_latest_quote(stock_name).ok())
+            .map(|quote| quote.timestamp)
+            .max()
+            .and_then(|timestamp| OffsetDateTime::from_unix_timestamp(timestamp as i64).ok())
+            .unwrap_or_else(OffsetDateTime::now_utc)
+    }
+
+    /// Fetches the quotes of a stock and derives its report
+    pub fn report(
+        &self,
+        stock_name: &str,
+        start_date: OffsetDateTime,
+        end_date: OffsetDateTime,
+        interval: Interval,
+    ) -> Result<StockReport, ProviderError> {
+        let quotes = self
+            .provider
+            .get_quote_history(stock_name, start_date, end_date, interval)?;
+        StockReport::new(stock_name, &quotes, interval, &self.volatility).ok_or_else(|| {
+            format!("No quotes found for {} in the selected range", stock_name).into()
+        })
+    }
+}
@@ -0,0 +1,442 @@
+use std::{
+    collections::HashSet,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Mutex,
+};
+
+use clap::ValueEnum;
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, Message, SmtpTransport,
+    Transport,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{alerts::Alert, indicators::Indicator, report::StockReport, volatility::Volatility};
+
+/// Something worth telling the user about
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Notification {
+    /// The max/min close and volatile bars of a stock over the selected window
+    Summary {
+        ticker: String,
+        max_close: f64,
+        max_close_time: String,
+        min_close: f64,
+        min_close_time: String,
+        volatile_bars: usize,
+        bars: usize,
+        /// The stats as printed on the command line
+        text: String,
+    },
+    /// The latest bar of a stock is volatile
+    Volatile {
+        ticker: String,
+        time: String,
+        open: f64,
+        high: f64,
+        low: f64,
+        close: f64,
+        measure: String,
+        threshold: f64,
+    },
+    Alert(Alert),
+}
+
+impl Notification {
+    pub fn summary(report: &StockReport, indicators: &[Indicator]) -> Self {
+        let time_format = report.interval.time_format();
+        Notification::Summary {
+            ticker: report.stock_name.clone(),
+            max_close: report.max_close.1,
+            max_close_time: report.max_close.0.format(time_format).to_string(),
+            min_close: report.min_close.1,
+            min_close_time: report.min_close.0.format(time_format).to_string(),
+            volatile_bars: report.volatile_days.len(),
+            bars: report.quotes.len(),
+            text: report.stats(indicators),
+        }
+    }
+
+    /// Returns None unless the latest bar of the report is volatile
+    pub fn volatile(report: &StockReport, volatility: &Volatility) -> Option<Self> {
+        let (latest, _) = report.quotes.last()?;
+        let (time, quote) = report.volatile_days.last().filter(|(t, _)| t == latest)?;
+        Some(Notification::Volatile {
+            ticker: report.stock_name.clone(),
+            time: time.format(report.interval.time_format()).to_string(),
+            open: quote.open,
+            high: quote.high,
+            low: quote.low,
+            close: quote.close,
+            measure: volatility
+                .measure
+                .to_possible_value()
+                .map(|value| value.get_name().to_owned())
+                .unwrap_or_default(),
+            threshold: volatility.threshold,
+        })
+    }
+
+    pub fn subject(&self) -> String {
+        match self {
+            Notification::Summary { ticker, .. } => format!("{} summary", ticker),
+            Notification::Volatile { ticker, time, .. } => {
+                format!("{} volatile on {}", ticker, time)
+            }
+            Notification::Alert(alert) => format!("{} alert: {}", alert.ticker, alert.rule),
+        }
+    }
+
+    pub fn body(&self) -> String {
+        match self {
+            Notification::Summary { text, .. } => text.clone(),
+            Notification::Volatile {
+                ticker,
+                time,
+                open,
+                high,
+                low,
+                close,
+                measure,
+                threshold,
+            } => format!(
+                "{} was volatile on {} by the {} measure (threshold {})\nOpen: ${:.2}\nHigh: ${:.2}\nLow: ${:.2}\nClose: ${:.2}\n",
+                ticker, time, measure, threshold, open, high, low, close
+            ),
+            Notification::Alert(alert) => format!("{}\n", alert),
+        }
+    }
+
+    /// Volatile bars are only notified once, even when the bar is updated by later polls
+    fn once_key(&self) -> Option<String> {
+        match self {
+            Notification::Volatile { ticker, time, .. } => Some(format!("{} {}", ticker, time)),
+            _ => None,
+        }
+    }
+}
+
+/// Delivers notifications to one destination
+pub trait Sink: Send + Sync {
+    fn send(&self, notification: &Notification) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+/// Prints the notifications on the command line
+pub struct StdoutSink;
+
+impl Sink for StdoutSink {
+    fn send(&self, notification: &Notification) -> Result<(), Box<dyn std::error::Error>> {
+        match notification {
+            Notification::Alert(alert) => println!("Alert: {}", alert),
+            _ => print!("{}", notification.body()),
+        }
+        Ok(())
+    }
+}
+
+/// Appends the notifications to a file
+pub struct LogFileSink(pub PathBuf);
+
+impl Sink for LogFileSink {
+    fn send(&self, notification: &Notification) -> Result<(), Box<dyn std::error::Error>> {
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.0)?;
+        match notification {
+            Notification::Alert(alert) => writeln!(file, "{}", alert)?,
+            _ => write!(file, "{}", notification.body())?,
+        }
+        Ok(())
+    }
+}
+
+/// Posts the notifications as JSON, with their `kind` next to their fields
+pub struct WebhookSink {
+    url: String,
+    client: reqwest::blocking::Client,
+}
+
+impl Sink for WebhookSink {
+    fn send(&self, notification: &Notification) -> Result<(), Box<dyn std::error::Error>> {
+        self.client
+            .post(&self.url)
+            .json(notification)
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Emails the notifications through an SMTP server
+pub struct EmailSink {
+    transport: SmtpTransport,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+}
+
+impl EmailSink {
+    fn new(config: &EmailConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let builder = if config.tls {
+            SmtpTransport::starttls_relay(&config.server)?
+        } else {
+            SmtpTransport::builder_dangerous(&config.server)
+        };
+        let mut builder = builder.port(config.port);
+        if let (Some(username), Some(password)) = (&config.username, &config.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+        Ok(EmailSink {
+            transport: builder.build(),
+            from: config.from.parse()?,
+            to: config
+                .to
+                .iter()
+                .map(|address| address.parse())
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl Sink for EmailSink {
+    fn send(&self, notification: &Notification) -> Result<(), Box<dyn std::error::Error>> {
+        let mut message = Message::builder()
+            .from(self.from.clone())
+            .subject(notification.subject());
+        for to in &self.to {
+            message = message.to(to.clone());
+        }
+        self.transport.send(&message.body(notification.body())?)?;
+        Ok(())
+    }
+}
+
+/// Runs a command for every notification, ex. notify-send for desktop notifications.
+/// `{subject}`, `{body}` and `{json}` in the arguments are replaced by the notification's.
+pub struct CommandSink(pub Vec<String>);
+
+impl Sink for CommandSink {
+    fn send(&self, notification: &Notification) -> Result<(), Box<dyn std::error::Error>> {
+        let (subject, body) = (notification.subject(), notification.body());
+        let json = serde_json::to_string(notification)?;
+        let args: Vec<String> = self.0[1..]
+            .iter()
+            .map(|arg| {
+                arg.replace("{subject}", &subject)
+                    .replace("{body}", &body)
+                    .replace("{json}", &json)
+            })
+            .collect();
+        let status = Command::new(&self.0[0]).args(args).status()?;
+        if !status.success() {
+            return Err(format!("{} exited with {}", self.0[0], status).into());
+        }
+        Ok(())
+    }
+}
+
+fn default_smtp_port() -> u16 {
+    587
+}
+
+fn default_tls() -> bool {
+    true
+}
+
+/// SMTP server and addresses of the email sink
+#[derive(Debug, Deserialize)]
+pub struct EmailConfig {
+    pub server: String,
+    #[serde(default = "default_smtp_port")]
+    pub port: u16,
+    pub from: String,
+    pub to: Vec<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Upgrades the connection with STARTTLS, only disable it for a local server
+    #[serde(default = "default_tls")]
+    pub tls: bool,
+}
+
+/// The sinks to deliver notifications to, as read from a JSON file
+#[derive(Debug, Default, Deserialize)]
+pub struct NotifyConfig {
+    /// File to which every notification is appended
+    pub log_file: Option<PathBuf>,
+    /// URL to which every notification is posted as JSON
+    pub webhook: Option<String>,
+    pub email: Option<EmailConfig>,
+    /// Program and arguments run for every notification
+    pub command: Option<Vec<String>>,
+}
+
+/// Sends notifications to every configured sink
+pub struct Notifier {
+    sinks: Vec<Box<dyn Sink>>,
+    sent: Mutex<HashSet<String>>,
+}
+
+impl Notifier {
+    pub fn new(config: &NotifyConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut sinks: Vec<Box<dyn Sink>> = Vec::new();
+        if let Some(log_file) = &config.log_file {
+            sinks.push(Box::new(LogFileSink(log_file.clone())));
+        }
+        if let Some(url) = &config.webhook {
+            sinks.push(Box::new(WebhookSink {
+                url: url.clone(),
+                client: reqwest::blocking::Client::new(),
+            }));
+        }
+        if let Some(email) = &config.email {
+            sinks.push(Box::new(EmailSink::new(email)?));
+        }
+        match config.command.as_deref() {
+            Some([]) => return Err("The notification command is empty".into()),
+            Some(command) => sinks.push(Box::new(CommandSink(command.to_vec()))),
+            None => {}
+        }
+        Ok(Notifier {
+            sinks,
+            sent: Mutex::new(HashSet::new()),
+        })
+    }
+
+    /// Reads the sinks from a JSON file
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let config: NotifyConfig = serde_json::from_str(&fs::read_to_string(path)?)?;
+        Notifier::new(&config)
+    }
+
+    /// Adds a sink, ex. to also print the notifications
+    pub fn with_sink(mut self, sink: impl Sink + 'static) -> Self {
+        self.sinks.insert(0, Box::new(sink));
+        self
+    }
+
+    /// Sends the notification to every sink, reporting the ones that failed
+    pub fn notify(&self, notification: &Notification) {
+        if let Some(key) = notification.once_key() {
+            if !self.sent.lock().unwrap().insert(key) {
+                return;
+            }
+        }
+        for sink in &self.sinks {
+            if let Err(error) = sink.send(notification) {
+                eprintln!(
+                    "Could not send the notification \"{}\": {}",
+                    notification.subject(),
+                    error
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Read,
+        net::TcpListener,
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    use super::*;
+
+    fn volatile(time: &str, close: f64) -> Notification {
+        Notification::Volatile {
+            ticker: "TEST".to_owned(),
+            time: time.to_owned(),
+            open: 100.0,
+            high: 105.5,
+            low: 95.0,
+            close,
+            measure: "range".to_owned(),
+            threshold: 0.02,
+        }
+    }
+
+    /// Keeps the subjects of the notifications it was sent
+    struct RecordingSink(Arc<Mutex<Vec<String>>>);
+
+    impl Sink for RecordingSink {
+        fn send(&self, notification: &Notification) -> Result<(), Box<dyn std::error::Error>> {
+            self.0.lock().unwrap().push(notification.subject());
+            Ok(())
+        }
+    }
+
+    /// Answers a single HTTP request with 200 and returns its body
+    fn receive_one_request(listener: TcpListener) -> String {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = Vec::new();
+        let mut buffer = [0; 1024];
+        loop {
+            let read = stream.read(&mut buffer).unwrap();
+            request.extend_from_slice(&buffer[..read]);
+            let text = String::from_utf8_lossy(&request).into_owned();
+            if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                let length = head
+                    .lines()
+                    .find_map(|line| {
+                        let (name, value) = line.split_once(':')?;
+                        name.eq_ignore_ascii_case("content-length")
+                            .then(|| value.trim().parse::<usize>().unwrap())
+                    })
+                    .unwrap_or(0);
+                if body.len() >= length || read == 0 {
+                    stream
+                        .write_all(
+                            b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                        )
+                        .unwrap();
+                    return body.to_owned();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn webhook_posts_the_kind_and_fields_as_json() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = thread::spawn(move || receive_one_request(listener));
+
+        let notifier = Notifier::new(&NotifyConfig {
+            webhook: Some(url),
+            ..NotifyConfig::default()
+        })
+        .unwrap();
+        notifier.notify(&volatile("2024-03-02", 104.0));
+
+        let payload: serde_json::Value = serde_json::from_str(&server.join().unwrap()).unwrap();
+        assert_eq!(payload["kind"], "volatile");
+        assert_eq!(payload["ticker"], "TEST");
+        assert_eq!(payload["time"], "2024-03-02");
+        assert_eq!(payload["high"], 105.5);
+        assert_eq!(payload["low"], 95.0);
+        assert_eq!(payload["close"], 104.0);
+        assert_eq!(payload["measure"], "range");
+        assert_eq!(payload["threshold"], 0.02);
+    }
+
+    #[test]
+    fn volatile_bar_is_notified_once() {
+        let subjects = Arc::new(Mutex::new(Vec::new()));
+        let notifier = Notifier::new(&NotifyConfig::default())
+            .unwrap()
+            .with_sink(RecordingSink(subjects.clone()));
+
+        // Later polls update the close of the same bar
+        notifier.notify(&volatile("2024-03-02", 104.0));
+        notifier.notify(&volatile("2024-03-02", 103.0));
+        notifier.notify(&volatile("2024-03-03", 97.0));
+
+        assert_eq!(
+            *subjects.lock().unwrap(),
+            ["TEST volatile on 2024-03-02", "TEST volatile on 2024-03-03"]
+        );
+    }
+}
@@ -0,0 +1,1282 @@
+use chrono::NaiveDateTime;
+use clap::ValueEnum;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::{io::Cursor, ops::Range};
+
+use image::{ImageFormat, RgbImage};
+use plotters::{
+    chart::{MeshStyle, SeriesLabelStyle},
+    coord::{ranged1d::ValueFormatter, types::RangedDateTime, CoordTranslate, Shift},
+    drawing::IntoDrawingArea,
+    prelude::*,
+    style::{
+        text_anchor::{HPos, Pos, VPos},
+        RGBColor,
+    },
+};
+
+use crate::{
+    backtest::BacktestResult,
+    benchmark::BenchmarkComparison,
+    correlation::CorrelationMatrix,
+    indicators::{self, Indicator},
+    portfolio::PortfolioReport,
+    report::StockReport,
+};
+
+/// Points of a line drawn on the time axis
+type Line = Vec<(NaiveDateTime, f64)>;
+
+/// Colors of the indicators drawn on top of the prices, distinct from the red price line
+const OVERLAY_COLORS: [RGBColor; 4] = [
+    RGBColor(0, 120, 200),
+    RGBColor(255, 140, 0),
+    RGBColor(150, 0, 200),
+    RGBColor(0, 160, 160),
+];
+
+/// Colors of the chart background and of the text, axes and gridlines drawn over it
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    /// Black on white
+    Light,
+    /// Light gray on dark gray
+    Dark,
+}
+
+impl Theme {
+    pub fn background(&self) -> RGBColor {
+        match self {
+            Theme::Light => WHITE,
+            Theme::Dark => RGBColor(30, 30, 30),
+        }
+    }
+
+    pub fn foreground(&self) -> RGBColor {
+        match self {
+            Theme::Light => BLACK,
+            Theme::Dark => RGBColor(220, 220, 220),
+        }
+    }
+}
+
+/// How the prices are spaced on the vertical axis of the price chart
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PriceScale {
+    /// Equal price differences are equally far apart
+    Linear,
+    /// Equal percent changes are equally far apart, for long ranges of growing stocks
+    Log,
+}
+
+/// Size, colors and axes of the charts, as read from the `[chart]` table of the config file
+/// Smallest width and height of a chart in pixels, leaving room for the labels
+pub const MIN_CHART_SIZE: u32 = 200;
+/// Largest width and height of a chart in pixels, keeping the image buffer in memory
+pub const MAX_CHART_SIZE: u32 = 10_000;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChartStyle {
+    /// Width of the charts in pixels
+    pub width: u32,
+    /// Height of the charts in pixels
+    pub height: u32,
+    pub theme: Theme,
+    /// Font family of the titles and labels
+    pub font: String,
+    /// Font size of the chart titles in pixels
+    pub caption_size: u32,
+    pub price_scale: PriceScale,
+    /// Draws the gridlines behind the series
+    pub gridlines: bool,
+    /// Room left above the highest and below the lowest price, in percent of the price range
+    pub price_padding: f64,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub price_color: RGBColor,
+    /// Color of the volatile bars' high to low bars
+    #[serde(deserialize_with = "deserialize_color")]
+    pub volatile_color: RGBColor,
+    /// Color of the candles and volume bars that closed at or above their open
+    #[serde(deserialize_with = "deserialize_color")]
+    pub up_color: RGBColor,
+    /// Color of the candles and volume bars that closed below their open
+    #[serde(deserialize_with = "deserialize_color")]
+    pub down_color: RGBColor,
+}
+
+impl ChartStyle {
+    /// Checks the size of the charts, which the config file can set to anything
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, size) in [("width", self.width), ("height", self.height)] {
+            if !(MIN_CHART_SIZE..=MAX_CHART_SIZE).contains(&size) {
+                return Err(format!(
+                    "The chart {} must be between {} and {} pixels",
+                    name, MIN_CHART_SIZE, MAX_CHART_SIZE
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for ChartStyle {
+    fn default() -> Self {
+        ChartStyle {
+            width: 800,
+            height: 600,
+            theme: Theme::Light,
+            font: "Arial".to_owned(),
+            caption_size: 30,
+            price_scale: PriceScale::Linear,
+            gridlines: true,
+            price_padding: 5.0,
+            price_color: RGBColor(255, 0, 0),
+            volatile_color: BLUE,
+            up_color: RGBColor(0, 150, 0),
+            down_color: RGBColor(200, 0, 0),
+        }
+    }
+}
+
+impl ChartStyle {
+    /// Font of the chart titles
+    fn caption_font(&self) -> TextStyle<'_> {
+        self.text_style(self.caption_size)
+    }
+
+    /// Font of the given size in the theme's text color
+    fn text_style(&self, size: u32) -> TextStyle<'_> {
+        (self.font.as_str(), size)
+            .into_font()
+            .color(&self.theme.foreground())
+    }
+}
+
+/// Applies the theme, font and gridlines of a chart style to the axes or the legend of a chart
+trait Themed<'b> {
+    fn themed(&mut self, style: &'b ChartStyle) -> &mut Self;
+}
+
+impl<'b, X: Ranged, Y: Ranged, DB: DrawingBackend> Themed<'b> for MeshStyle<'_, 'b, X, Y, DB> {
+    fn themed(&mut self, style: &'b ChartStyle) -> &mut Self {
+        let foreground = style.theme.foreground();
+        self.axis_style(foreground)
+            .label_style(style.text_style(12))
+            .bold_line_style(foreground.mix(0.2))
+            .light_line_style(foreground.mix(0.1));
+        if !style.gridlines {
+            self.disable_mesh();
+        }
+        self
+    }
+}
+
+impl<'a, 'b, DB: DrawingBackend + 'a, CT: CoordTranslate> Themed<'b>
+    for SeriesLabelStyle<'a, 'b, DB, CT>
+{
+    fn themed(&mut self, style: &'b ChartStyle) -> &mut Self {
+        self.background_style(style.theme.background().mix(0.8))
+            .border_style(style.theme.foreground())
+            .label_font(style.text_style(12))
+    }
+}
+
+/// Parses a color written as `#rrggbb`
+pub fn parse_color(s: &str) -> Result<RGBColor, String> {
+    let hex = s
+        .strip_prefix('#')
+        .filter(|hex| hex.len() == 6)
+        .ok_or_else(|| format!("Invalid color {}, expected #rrggbb", s))?;
+    let channel = |i: usize| {
+        u8::from_str_radix(&hex[i..i + 2], 16)
+            .map_err(|_| format!("Invalid color {}, expected #rrggbb", s))
+    };
+    Ok(RGBColor(channel(0)?, channel(2)?, channel(4)?))
+}
+
+fn deserialize_color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<RGBColor, D::Error> {
+    let color = String::deserialize(deserializer)?;
+    parse_color(&color).map_err(serde::de::Error::custom)
+}
+
+/// How the prices of a stock are drawn
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum ChartKind {
+    /// A line of the closing prices
+    Line,
+    /// Open, high, low and close of every bar, green when the price went up and red when it went down
+    Candlestick,
+}
+
+/// What is drawn on the price chart of a stock
+#[derive(Clone, Copy)]
+pub struct PriceChart<'a> {
+    pub kind: ChartKind,
+    /// Marks the volatile bars with their high to low range
+    pub show_volatility: bool,
+    pub indicators: &'a [Indicator],
+}
+
+/// File format the charts are rendered to
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum ChartFormat {
+    /// PNG image
+    Png,
+    /// Scalable vector graphics
+    Svg,
+    /// Self-contained HTML page of the SVG chart, showing the open, high, low, close and volume
+    /// of the bar under the mouse on stock price charts
+    Html,
+}
+
+impl ChartFormat {
+    /// Format matching the extension of a chart file or endpoint, ex. `.svg`
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        ChartFormat::from_str(extension, true).ok()
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ChartFormat::Png => "png",
+            ChartFormat::Svg => "svg",
+            ChartFormat::Html => "html",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ChartFormat::Png => "image/png",
+            ChartFormat::Svg => "image/svg+xml",
+            ChartFormat::Html => "text/html; charset=utf-8",
+        }
+    }
+}
+
+/// A bar of an interactive chart, shown in a tooltip while the mouse is over its pixel column
+#[derive(Serialize)]
+pub struct HoverBar {
+    x: i32,
+    time: String,
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+    volume: u64,
+}
+
+impl HoverBar {
+    /// Pairs the bars of the report with their pixel columns, as returned by `plot_prices`
+    pub fn from_report(report: &StockReport, columns: &[i32]) -> Vec<HoverBar> {
+        let time_format = report.interval.time_format();
+        report
+            .quotes
+            .iter()
+            .zip(columns)
+            .map(|((time, quote), x)| HoverBar {
+                x: *x,
+                time: time.format(time_format).to_string(),
+                open: quote.open,
+                high: quote.high,
+                low: quote.low,
+                close: quote.close,
+                volume: quote.volume,
+            })
+            .collect()
+    }
+}
+
+/// Draws a chart of the style's size in memory and encodes it as a PNG image
+fn render_png(
+    style: &ChartStyle,
+    draw: impl FnOnce(&DrawingArea<BitMapBackend, Shift>) -> Result<(), Box<dyn std::error::Error>>,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let (width, height) = (style.width, style.height);
+    let size = (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|pixels| pixels.checked_mul(3))
+        .ok_or("The chart is too large")?;
+    let mut pixels = vec![0; size];
+    {
+        let root = BitMapBackend::with_buffer(&mut pixels, (width, height)).into_drawing_area();
+        draw(&root)?;
+        root.present()?;
+    }
+    let image =
+        RgbImage::from_raw(width, height, pixels).ok_or("Chart buffer has the wrong size")?;
+    let mut png = Cursor::new(Vec::new());
+    image.write_to(&mut png, ImageFormat::Png)?;
+    Ok(png.into_inner())
+}
+
+/// Draws a chart of the style's size as an SVG document
+fn render_svg(
+    style: &ChartStyle,
+    draw: impl FnOnce(&DrawingArea<SVGBackend, Shift>) -> Result<(), Box<dyn std::error::Error>>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut svg = String::new();
+    {
+        let root =
+            SVGBackend::with_string(&mut svg, (style.width, style.height)).into_drawing_area();
+        draw(&root)?;
+        root.present()?;
+    }
+    Ok(svg)
+}
+
+/// Embeds an SVG chart in an HTML page that needs no other file, with a tooltip over each of
+/// the bars when there are any
+fn render_html(svg: &str, bars: &[HoverBar]) -> String {
+    // Built in so that the page can be written anywhere by the plot command
+    include_str!("chart.html")
+        .replace("{{bars}}", &serde_json::to_string(bars).unwrap())
+        .replace("{{svg}}", svg)
+}
+
+/// A chart with everything needed to draw it on any backend
+pub enum Chart<'a> {
+    Prices {
+        min_date: NaiveDateTime,
+        max_date: NaiveDateTime,
+        report: &'a StockReport,
+        layers: PriceChart<'a>,
+    },
+    Comparison {
+        min_date: NaiveDateTime,
+        max_date: NaiveDateTime,
+        reports: &'a [StockReport],
+    },
+    Correlation(&'a CorrelationMatrix),
+    Portfolio(&'a PortfolioReport),
+    Equity {
+        min_date: NaiveDateTime,
+        max_date: NaiveDateTime,
+        result: &'a BacktestResult,
+    },
+    Benchmark {
+        min_date: NaiveDateTime,
+        max_date: NaiveDateTime,
+        comparison: &'a BenchmarkComparison,
+    },
+}
+
+impl Chart<'_> {
+    /// Draws the chart, returning the pixel column of every bar of a price chart
+    fn draw<DB: DrawingBackend>(
+        &self,
+        root: &DrawingArea<DB, Shift>,
+        style: &ChartStyle,
+    ) -> Result<Vec<i32>, Box<dyn std::error::Error>>
+    where
+        DB::ErrorType: 'static,
+    {
+        match *self {
+            Chart::Prices {
+                min_date,
+                max_date,
+                report,
+                ref layers,
+            } => plot_prices(root, min_date, max_date, report, layers, style),
+            Chart::Comparison {
+                min_date,
+                max_date,
+                reports,
+            } => plot_comparison(root, min_date, max_date, reports, style).map(|_| Vec::new()),
+            Chart::Correlation(matrix) => plot_correlation(root, matrix, style).map(|_| Vec::new()),
+            Chart::Portfolio(report) => plot_portfolio(root, report, style).map(|_| Vec::new()),
+            Chart::Equity {
+                min_date,
+                max_date,
+                result,
+            } => plot_equity(root, min_date, max_date, result, style).map(|_| Vec::new()),
+            Chart::Benchmark {
+                min_date,
+                max_date,
+                comparison,
+            } => plot_benchmark(root, min_date, max_date, comparison, style).map(|_| Vec::new()),
+        }
+    }
+}
+
+/// Draws the chart as a PNG image, an SVG image or an interactive HTML page. The HTML page of
+/// a price chart shows a tooltip over each bar.
+pub fn render_chart(
+    chart: &Chart,
+    format: ChartFormat,
+    style: &ChartStyle,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    match format {
+        ChartFormat::Png => render_png(style, |root| chart.draw(root, style).map(|_| ())),
+        ChartFormat::Svg => {
+            render_svg(style, |root| chart.draw(root, style).map(|_| ())).map(String::into_bytes)
+        }
+        ChartFormat::Html => {
+            // The tooltips need the pixel columns of the bars, known once the chart is drawn
+            let mut columns = Vec::new();
+            let svg = render_svg(style, |root| {
+                columns = chart.draw(root, style)?;
+                Ok(())
+            })?;
+            let bars = match chart {
+                Chart::Prices { report, .. } => HoverBar::from_report(report, &columns),
+                _ => Vec::new(),
+            };
+            Ok(render_html(&svg, &bars).into_bytes())
+        }
+    }
+}
+
+fn plot_prices<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    min_date: NaiveDateTime,
+    max_date: NaiveDateTime,
+    report: &StockReport,
+    layers: &PriceChart,
+    style: &ChartStyle,
+) -> Result<Vec<i32>, Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let PriceChart {
+        kind, indicators, ..
+    } = *layers;
+
+    // Split the drawing area into the prices above a panel per oscillator and the volume
+    root.fill(&style.theme.background())?;
+    let root = root.titled(
+        &(report.stock_name.to_owned() + " Stock Prices"),
+        style.caption_font(),
+    )?;
+    let oscillators: Vec<&Indicator> = indicators.iter().filter(|i| !i.is_overlay()).collect();
+    // Oscillators get a sixth of the height each but share half of it when there are many,
+    // leaving at least a quarter for the prices
+    let height = root.dim_in_pixel().1;
+    let panel = (height / 6).min(height / 2 / oscillators.len().max(1) as u32);
+    let mut breakpoints =
+        vec![(height - height / 4).saturating_sub(oscillators.len() as u32 * panel)];
+    for _ in &oscillators {
+        breakpoints.push(breakpoints[breakpoints.len() - 1] + panel);
+    }
+    let areas = root.split_by_breakpoints::<u32, u32, _, _>([], breakpoints);
+    let (price_area, volume_area) = (&areas[0], &areas[areas.len() - 1]);
+
+    // Candlesticks need room for the wicks above and below the closing prices
+    let (mut min_price, mut max_price) = match kind {
+        ChartKind::Line => (report.min_close.1, report.max_close.1),
+        ChartKind::Candlestick => (
+            report
+                .quotes
+                .iter()
+                .map(|(_, q)| q.low)
+                .fold(f64::INFINITY, f64::min),
+            report
+                .quotes
+                .iter()
+                .map(|(_, q)| q.high)
+                .fold(f64::NEG_INFINITY, f64::max),
+        ),
+    };
+
+    // Compute the overlays, widening the price axis so that Bollinger Bands are not clipped
+    let quotes = report.raw_quotes();
+    let overlays: Vec<(String, Vec<Line>)> = indicators
+        .iter()
+        .filter(|i| i.is_overlay())
+        .map(|indicator| {
+            let lines = match *indicator {
+                Indicator::Sma(period) => vec![indicators::sma(&quotes, period)],
+                Indicator::Ema(period) => vec![indicators::ema(&quotes, period)],
+                Indicator::Bollinger { period, deviations } => {
+                    let bands = indicators::bollinger_bands(&quotes, period, deviations);
+                    vec![
+                        bands.iter().map(|b| b.map(|b| b.upper)).collect(),
+                        bands.iter().map(|b| b.map(|b| b.middle)).collect(),
+                        bands.iter().map(|b| b.map(|b| b.lower)).collect(),
+                    ]
+                }
+                _ => Vec::new(),
+            };
+            // A logarithmic axis cannot show the values at or below zero, ex. a wide lower band
+            let lines = lines
+                .into_iter()
+                .map(|values| {
+                    with_times(report, values)
+                        .filter(|(_, y)| style.price_scale == PriceScale::Linear || *y > 0.0)
+                        .collect()
+                })
+                .collect();
+            (indicator.to_string(), lines)
+        })
+        .collect();
+    for (_, y) in overlays
+        .iter()
+        .flat_map(|(_, lines)| lines.iter().flatten())
+    {
+        min_price = min_price.min(*y);
+        max_price = max_price.max(*y);
+    }
+
+    // Configure the price chart with room around the prices, the dates are labelled below the
+    // volume chart
+    if style.price_scale == PriceScale::Log && min_price <= 0.0 {
+        return Err("The log price scale needs positive prices".into());
+    }
+    let padding = style.price_padding.max(0.0) / 100.0;
+    let (min_price, max_price) = pad_prices(min_price, max_price, padding, style.price_scale);
+    let columns = match style.price_scale {
+        PriceScale::Linear => {
+            let chart = ChartBuilder::on(price_area)
+                .y_label_area_size(60)
+                .build_cartesian_2d(
+                    RangedDateTime::from(min_date..max_date),
+                    min_price..max_price,
+                )?;
+            draw_prices(chart, report, layers, overlays, style)?
+        }
+        PriceScale::Log => {
+            // Label about 8 prices the same distance apart rather than only the powers of 10
+            let base = (max_price / min_price).powf(1.0 / 8.0).max(1.001);
+            let chart = ChartBuilder::on(price_area)
+                .y_label_area_size(60)
+                .build_cartesian_2d(
+                    RangedDateTime::from(min_date..max_date),
+                    (min_price..max_price).log_scale().base(base),
+                )?;
+            draw_prices(chart, report, layers, overlays, style)?
+        }
+    };
+
+    for (indicator, area) in oscillators.iter().zip(&areas[1..]) {
+        plot_oscillator(area, min_date, max_date, report, &quotes, indicator, style)?;
+    }
+    plot_volume(volume_area, min_date, max_date, report, style)?;
+
+    Ok(columns)
+}
+
+/// Widens the price axis by `padding` times the price range above and below the prices. A flat
+/// series has no range, so it gets that share of the price itself, or at least a cent.
+fn pad_prices(min_price: f64, max_price: f64, padding: f64, scale: PriceScale) -> (f64, f64) {
+    match scale {
+        PriceScale::Linear => {
+            let margin = if max_price > min_price {
+                (max_price - min_price) * padding
+            } else {
+                (min_price.abs() * padding).max(0.01)
+            };
+            (min_price - margin, max_price + margin)
+        }
+        // Padding the logarithm of the prices keeps the axis above zero
+        PriceScale::Log => {
+            let ratio = if max_price > min_price {
+                (max_price / min_price).powf(padding)
+            } else {
+                1.0 + padding.max(0.01)
+            };
+            (min_price / ratio, max_price * ratio)
+        }
+    }
+}
+
+/// Draws the prices, the volatile bars and the overlaid indicators on the linear or logarithmic
+/// price axis, returning the pixel column of every bar where the interactive chart shows its
+/// tooltip
+fn draw_prices<'a, DB: DrawingBackend + 'a, Y>(
+    mut chart: ChartContext<'a, DB, Cartesian2d<RangedDateTime<NaiveDateTime>, Y>>,
+    report: &StockReport,
+    layers: &PriceChart,
+    overlays: Vec<(String, Vec<Line>)>,
+    style: &ChartStyle,
+) -> Result<Vec<i32>, Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+    Y: Ranged<ValueType = f64> + ValueFormatter<f64>,
+{
+    let format_price = |price: &f64| format!("{:.2}", price);
+    let mut mesh = chart.configure_mesh();
+    mesh.themed(style);
+    if style.price_scale == PriceScale::Log {
+        mesh.y_label_formatter(&format_price);
+    }
+    mesh.draw()?;
+
+    match layers.kind {
+        // Draw the line series
+        ChartKind::Line => {
+            chart.draw_series(LineSeries::new(
+                report.series.iter().map(|(x, y)| (*x, *y)),
+                &style.price_color,
+            ))?;
+        }
+        // Draw one candle per bar, narrow enough that neighbouring candles do not overlap
+        ChartKind::Candlestick => {
+            let candle_width =
+                (style.width * 7 / 8 / report.quotes.len().max(1) as u32).clamp(1, 15);
+            chart.draw_series(report.quotes.iter().map(|(x, q)| {
+                CandleStick::new(
+                    *x,
+                    q.open,
+                    q.high,
+                    q.low,
+                    q.close,
+                    style.up_color.filled(),
+                    style.down_color.filled(),
+                    candle_width,
+                )
+            }))?;
+        }
+    }
+
+    // Draw the volatility data
+    if layers.show_volatility {
+        chart.draw_series(report.volatile_days.iter().map(|(x, y)| {
+            ErrorBar::new_vertical(
+                *x,
+                y.low,
+                y.close,
+                y.high,
+                style.volatile_color.filled(),
+                10,
+            )
+        }))?;
+    }
+
+    // Draw the overlaid indicators with a legend entry each
+    let has_overlays = !overlays.is_empty();
+    for (i, (label, lines)) in overlays.into_iter().enumerate() {
+        let color = OVERLAY_COLORS[i % OVERLAY_COLORS.len()];
+        for (j, line) in lines.into_iter().enumerate() {
+            let series = chart.draw_series(LineSeries::new(line, &color))?;
+            if j == 0 {
+                series
+                    .label(label.as_str())
+                    .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+            }
+        }
+    }
+    if has_overlays {
+        chart
+            .configure_series_labels()
+            .position(SeriesLabelPosition::UpperLeft)
+            .themed(style)
+            .draw()?;
+    }
+
+    Ok(report
+        .quotes
+        .iter()
+        .map(|(x, q)| chart.backend_coord(&(*x, q.close)).0)
+        .collect())
+}
+
+/// Pairs indicator values with the times of the report's bars, skipping missing values
+fn with_times(
+    report: &StockReport,
+    values: Vec<Option<f64>>,
+) -> impl Iterator<Item = (NaiveDateTime, f64)> + '_ {
+    report
+        .quotes
+        .iter()
+        .zip(values)
+        .filter_map(|((x, _), value)| Some((*x, value?)))
+}
+
+/// Draws RSI, MACD or ATR in its own panel on the same time axis as the prices
+fn plot_oscillator<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    min_date: NaiveDateTime,
+    max_date: NaiveDateTime,
+    report: &StockReport,
+    quotes: &[yahoo_finance_api::Quote],
+    indicator: &Indicator,
+    style: &ChartStyle,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    // Each oscillator is made of lines and, for MACD, a histogram
+    let (lines, histogram): (Vec<Line>, Line) = match *indicator {
+        Indicator::Rsi(period) => (
+            vec![with_times(report, indicators::rsi(quotes, period)).collect()],
+            Vec::new(),
+        ),
+        Indicator::Atr(period) => (
+            vec![with_times(report, indicators::atr(quotes, period)).collect()],
+            Vec::new(),
+        ),
+        Indicator::Macd { fast, slow, signal } => {
+            let points = indicators::macd(quotes, fast, slow, signal);
+            let line = |f: fn(&indicators::MacdPoint) -> f64| -> Line {
+                with_times(report, points.iter().map(|p| p.as_ref().map(f)).collect()).collect()
+            };
+            (
+                vec![line(|p| p.macd), line(|p| p.signal)],
+                line(|p| p.histogram),
+            )
+        }
+        _ => return Ok(()),
+    };
+
+    // RSI is always shown on its full scale with the usual 30/70 levels
+    let (min_value, max_value) = match indicator {
+        Indicator::Rsi(_) => (0.0, 100.0),
+        _ => lines
+            .iter()
+            .chain([&histogram])
+            .flatten()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), (_, y)| {
+                (min.min(*y), max.max(*y))
+            }),
+    };
+    if min_value > max_value {
+        return Ok(());
+    }
+
+    let mut chart = ChartBuilder::on(area)
+        .y_label_area_size(60)
+        .caption(indicator.to_string(), style.text_style(12))
+        .build_cartesian_2d(
+            RangedDateTime::from(min_date..max_date),
+            min_value..max_value,
+        )?;
+    chart.configure_mesh().themed(style).y_labels(3).draw()?;
+
+    if let Indicator::Rsi(_) = indicator {
+        for level in [30.0, 70.0] {
+            chart.draw_series(LineSeries::new(
+                [(min_date, level), (max_date, level)],
+                &RGBColor(150, 150, 150),
+            ))?;
+        }
+    }
+    let half_bar = report.interval.duration() * 2 / 5;
+    chart.draw_series(histogram.iter().map(|(x, y)| {
+        Rectangle::new(
+            [(*x - half_bar, 0.0), (*x + half_bar, *y)],
+            RGBColor(150, 150, 150).mix(0.6).filled(),
+        )
+    }))?;
+    for (i, line) in lines.into_iter().enumerate() {
+        let color = if i == 0 { BLUE } else { RGBColor(255, 140, 0) };
+        chart.draw_series(LineSeries::new(line, &color))?;
+    }
+
+    Ok(())
+}
+
+/// Number of bars averaged by the volume moving average
+const VOLUME_AVERAGE_BARS: usize = 20;
+
+/// Draws a histogram of the traded volume, colored by up/down bar, with its moving average
+fn plot_volume<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    min_date: NaiveDateTime,
+    max_date: NaiveDateTime,
+    report: &StockReport,
+    style: &ChartStyle,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let max_volume = report
+        .quotes
+        .iter()
+        .map(|(_, q)| q.volume)
+        .max()
+        .unwrap_or(0);
+
+    let mut chart = ChartBuilder::on(area)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .build_cartesian_2d(
+            RangedDateTime::from(min_date..max_date),
+            0.0..(max_volume.max(1) as f64),
+        )?;
+
+    let time_format = report.interval.axis_format();
+    chart
+        .configure_mesh()
+        .themed(style)
+        .y_labels(3)
+        .x_label_formatter(&|x| x.format(time_format).to_string())
+        .y_label_formatter(&|y| format_volume(*y))
+        .draw()?;
+
+    // Each bar covers most of its interval, leaving a gap to the next one
+    let half_bar = report.interval.duration() * 2 / 5;
+    chart.draw_series(report.quotes.iter().map(|(x, q)| {
+        let color = if q.close >= q.open {
+            style.up_color
+        } else {
+            style.down_color
+        };
+        Rectangle::new(
+            [(*x - half_bar, 0.0), (*x + half_bar, q.volume as f64)],
+            color.mix(0.6).filled(),
+        )
+    }))?;
+
+    // Draw the moving average once enough bars are available
+    let volumes: Vec<f64> = report.quotes.iter().map(|(_, q)| q.volume as f64).collect();
+    chart.draw_series(LineSeries::new(
+        with_times(
+            report,
+            indicators::moving_average(&volumes, VOLUME_AVERAGE_BARS),
+        ),
+        &BLUE,
+    ))?;
+
+    Ok(())
+}
+
+/// Shortens large volumes and values for the axis labels, ex. 12500000 to 12.5M
+fn format_volume(volume: f64) -> String {
+    if volume >= 1e9 {
+        format!("{:.1}B", volume / 1e9)
+    } else if volume >= 1e6 {
+        format!("{:.1}M", volume / 1e6)
+    } else if volume >= 1e3 {
+        format!("{:.1}K", volume / 1e3)
+    } else {
+        format!("{:.0}", volume)
+    }
+}
+
+/// Overlays several stocks on one chart as the percent change since their first close
+fn plot_comparison<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    min_date: NaiveDateTime,
+    max_date: NaiveDateTime,
+    reports: &[StockReport],
+    style: &ChartStyle,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    // Rebase every series to its first closing price
+    let changes: Vec<Line> = reports
+        .iter()
+        .map(|report| {
+            let first_close = report.series[0].1;
+            report
+                .series
+                .iter()
+                .map(|(date, close)| (*date, (close / first_close - 1.0) * 100.0))
+                .collect()
+        })
+        .collect();
+    let min_change = changes
+        .iter()
+        .flatten()
+        .map(|(_, c)| *c)
+        .fold(f64::INFINITY, f64::min);
+    let max_change = changes
+        .iter()
+        .flatten()
+        .map(|(_, c)| *c)
+        .fold(f64::NEG_INFINITY, f64::max);
+
+    root.fill(&style.theme.background())?;
+
+    let stock_names: Vec<&str> = reports.iter().map(|r| r.stock_name.as_str()).collect();
+    let mut chart = ChartBuilder::on(root)
+        .caption(stock_names.join(" vs ") + " % Change", style.caption_font())
+        .x_label_area_size(40)
+        .y_label_area_size(40)
+        .build_cartesian_2d(
+            RangedDateTime::from(min_date..max_date),
+            min_change..max_change,
+        )?;
+    let time_format = reports[0].interval.axis_format();
+    chart
+        .configure_mesh()
+        .themed(style)
+        .x_label_formatter(&|x| x.format(time_format).to_string())
+        .draw()?;
+
+    // Draw one line per stock with a legend entry
+    for (i, (report, change)) in reports.iter().zip(changes).enumerate() {
+        let color = Palette99::pick(i).to_rgba();
+        chart
+            .draw_series(LineSeries::new(change, &color))?
+            .label(&report.stock_name)
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+    }
+    chart.configure_series_labels().themed(style).draw()?;
+
+    Ok(())
+}
+
+/// A chart of two lines over time, ex. a strategy against buying and holding its stock
+struct TwoLines<'a> {
+    caption: String,
+    /// Legend labels of the colored line and of the grey line
+    labels: (&'a str, &'a str),
+    /// The time and the values of both lines at every point
+    points: &'a [(NaiveDateTime, f64, f64)],
+    x_range: Range<NaiveDateTime>,
+    y_range: Range<f64>,
+    time_format: &'a str,
+    y_label_area: u32,
+    format_y: fn(f64) -> String,
+}
+
+/// Draws the first line in color and the second in grey, with a legend in the upper left
+fn plot_two_lines<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    lines: TwoLines,
+    style: &ChartStyle,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    root.fill(&style.theme.background())?;
+    let mut chart = ChartBuilder::on(root)
+        .caption(lines.caption, style.caption_font())
+        .x_label_area_size(40)
+        .y_label_area_size(lines.y_label_area)
+        .build_cartesian_2d(RangedDateTime::from(lines.x_range), lines.y_range)?;
+    chart
+        .configure_mesh()
+        .themed(style)
+        .x_label_formatter(&|x| x.format(lines.time_format).to_string())
+        .y_label_formatter(&|y| (lines.format_y)(*y))
+        .draw()?;
+
+    let (first_color, second_color) = (OVERLAY_COLORS[0], RGBColor(128, 128, 128));
+    chart
+        .draw_series(LineSeries::new(
+            lines.points.iter().map(|(time, first, _)| (*time, *first)),
+            &first_color,
+        ))?
+        .label(lines.labels.0)
+        .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], first_color));
+    chart
+        .draw_series(LineSeries::new(
+            lines
+                .points
+                .iter()
+                .map(|(time, _, second)| (*time, *second)),
+            &second_color,
+        ))?
+        .label(lines.labels.1)
+        .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], second_color));
+    chart
+        .configure_series_labels()
+        .position(SeriesLabelPosition::UpperLeft)
+        .themed(style)
+        .draw()?;
+
+    Ok(())
+}
+
+/// The lowest and highest value of both lines
+fn value_range(points: &[(NaiveDateTime, f64, f64)]) -> Range<f64> {
+    let (min, max) = points.iter().fold(
+        (f64::INFINITY, f64::NEG_INFINITY),
+        |(min, max), (_, first, second)| (min.min(first.min(*second)), max.max(first.max(*second))),
+    );
+    min..max
+}
+
+/// Draws the market value of a portfolio over time along with the cost of the lots held
+fn plot_portfolio<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    report: &PortfolioReport,
+    style: &ChartStyle,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let (first, last) = match (report.values.first(), report.values.last()) {
+        (Some(first), Some(last)) => (first.0, last.0),
+        _ => return Err("The portfolio has no quotes".into()),
+    };
+    let max_value = report
+        .values
+        .iter()
+        .map(|(_, value, cost)| value.max(*cost))
+        .fold(0.0, f64::max);
+    let lines = TwoLines {
+        caption: "Portfolio Value".to_owned(),
+        labels: ("Value", "Cost"),
+        points: &report.values,
+        x_range: first..last,
+        y_range: 0.0..max_value * 1.05,
+        time_format: "%Y-%m-%d",
+        y_label_area: 70,
+        format_y: |y| format!("${}", format_volume(y)),
+    };
+    plot_two_lines(root, lines, style)
+}
+
+/// Draws the equity of a backtested strategy next to buying and holding the stock
+fn plot_equity<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    min_date: NaiveDateTime,
+    max_date: NaiveDateTime,
+    result: &BacktestResult,
+    style: &ChartStyle,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let lines = TwoLines {
+        caption: format!("{} {}", result.stock_name, result.strategy),
+        labels: ("Strategy", "Buy and hold"),
+        points: &result.equity,
+        x_range: min_date..max_date,
+        y_range: value_range(&result.equity),
+        time_format: result.interval.axis_format(),
+        y_label_area: 70,
+        format_y: |y| format!("${}", format_volume(y)),
+    };
+    plot_two_lines(root, lines, style)
+}
+
+/// Draws the closes of a stock and of its benchmark rebased to 100
+fn plot_benchmark<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    min_date: NaiveDateTime,
+    max_date: NaiveDateTime,
+    comparison: &BenchmarkComparison,
+    style: &ChartStyle,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let lines = TwoLines {
+        caption: format!("{} vs {}", comparison.stock_name, comparison.benchmark),
+        labels: (&comparison.stock_name, &comparison.benchmark),
+        points: &comparison.rebased,
+        x_range: min_date..max_date,
+        y_range: value_range(&comparison.rebased),
+        time_format: comparison.interval.axis_format(),
+        y_label_area: 60,
+        format_y: |y| format!("{:.0}", y),
+    };
+    plot_two_lines(root, lines, style)
+}
+
+/// Draws the correlation matrix as a grid of cells from blue (-1) through white to red (1)
+fn plot_correlation<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    matrix: &CorrelationMatrix,
+    style: &ChartStyle,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB::ErrorType: 'static,
+{
+    root.fill(&style.theme.background())?;
+    let size = matrix.stock_names.len();
+    let mut chart = ChartBuilder::on(root)
+        .caption("Correlation of Returns", style.caption_font())
+        .margin(10)
+        .x_label_area_size(40)
+        .y_label_area_size(70)
+        .build_cartesian_2d(
+            (0..size - 1).into_segmented(),
+            (0..size - 1).into_segmented(),
+        )?;
+    // The first stock is on the top row, matching the order of the columns
+    let label = |value: &SegmentValue<usize>, reversed: bool| match value {
+        SegmentValue::CenterOf(i) if *i < size => {
+            matrix.stock_names[if reversed { size - 1 - i } else { *i }].clone()
+        }
+        _ => String::new(),
+    };
+    chart
+        .configure_mesh()
+        .themed(style)
+        .disable_mesh()
+        .x_labels(size)
+        .y_labels(size)
+        .x_label_formatter(&|x| label(x, false))
+        .y_label_formatter(&|y| label(y, true))
+        .draw()?;
+
+    for (row, values) in matrix.values.iter().enumerate() {
+        let y = size - 1 - row;
+        for (x, value) in values.iter().enumerate() {
+            let color = match value {
+                Some(v) if *v >= 0.0 => {
+                    let fade = (255.0 * (1.0 - v.min(1.0))) as u8;
+                    RGBColor(255, fade, fade)
+                }
+                Some(v) => {
+                    let fade = (255.0 * (1.0 + v.max(-1.0))) as u8;
+                    RGBColor(fade, fade, 255)
+                }
+                None => RGBColor(200, 200, 200),
+            };
+            chart.draw_series(std::iter::once(Rectangle::new(
+                [
+                    (SegmentValue::Exact(x), SegmentValue::Exact(y)),
+                    (SegmentValue::Exact(x + 1), SegmentValue::Exact(y + 1)),
+                ],
+                color.filled(),
+            )))?;
+            let text = value.map_or("-".to_owned(), |v| format!("{:.2}", v));
+            chart.draw_series(std::iter::once(Text::new(
+                text,
+                (SegmentValue::CenterOf(x), SegmentValue::CenterOf(y)),
+                (style.font.as_str(), 20)
+                    .into_font()
+                    .color(&BLACK)
+                    .pos(Pos::new(HPos::Center, VPos::Center)),
+            )))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use yahoo_finance_api::Quote;
+
+    use super::*;
+    use crate::{
+        timeframe::Interval,
+        volatility::{Volatility, VolatilityMeasure},
+    };
+
+    fn report(closes: &[f64]) -> StockReport {
+        let quotes: Vec<Quote> = closes
+            .iter()
+            .enumerate()
+            .map(|(day, close)| Quote {
+                timestamp: 1_709_251_200 + day as u64 * 86_400,
+                open: *close,
+                high: close * 1.5,
+                low: close * 0.5,
+                volume: 1000,
+                close: *close,
+                adjclose: *close,
+            })
+            .collect();
+        let volatility = Volatility::new(VolatilityMeasure::Range, None, 20);
+        StockReport::new("TEST", &quotes, Interval::OneDay, &volatility).unwrap()
+    }
+
+    /// Draws the prices of the report over one more day than its bars
+    fn render_prices(report: &StockReport, indicators: &[Indicator], style: &ChartStyle) -> String {
+        let layers = PriceChart {
+            kind: ChartKind::Line,
+            show_volatility: false,
+            indicators,
+        };
+        let min_date = report.series[0].0;
+        let max_date = report.series[report.series.len() - 1].0 + chrono::Duration::days(1);
+        render_svg(style, |root| {
+            plot_prices(root, min_date, max_date, report, &layers, style).map(|_| ())
+        })
+        .unwrap()
+    }
+
+    /// The numbers written on the chart, ex. the price and volume labels
+    fn labels(svg: &str) -> Vec<f64> {
+        svg.split("</text>")
+            .filter_map(|text| text.rsplit('>').next()?.trim().parse().ok())
+            .collect()
+    }
+
+    #[test]
+    fn html_prices_have_a_tooltip_per_bar() {
+        let report = report(&[50.0, 51.0, 49.0]);
+        let chart = Chart::Prices {
+            min_date: report.series[0].0,
+            max_date: report.series[2].0,
+            report: &report,
+            layers: PriceChart {
+                kind: ChartKind::Line,
+                show_volatility: false,
+                indicators: &[],
+            },
+        };
+        let html = String::from_utf8(
+            render_chart(&chart, ChartFormat::Html, &ChartStyle::default()).unwrap(),
+        )
+        .unwrap();
+        let bars = html
+            .split("const bars = ")
+            .nth(1)
+            .unwrap()
+            .split(';')
+            .next()
+            .unwrap();
+        let bars: Vec<serde_json::Value> = serde_json::from_str(bars).unwrap();
+        assert_eq!(bars.len(), 3);
+        assert!(bars[0]["x"].as_i64().unwrap() < bars[2]["x"].as_i64().unwrap());
+        assert_eq!(bars[1]["close"], 51.0);
+    }
+
+    #[test]
+    fn chart_size_is_bounded() {
+        assert_eq!(ChartStyle::default().validate(), Ok(()));
+        for (width, height) in [(0, 600), (800, 199), (40_000, 40_000)] {
+            let style = ChartStyle {
+                width,
+                height,
+                ..ChartStyle::default()
+            };
+            assert!(style.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn flat_prices_are_padded_by_a_share_of_the_price() {
+        assert_eq!(
+            pad_prices(50.0, 50.0, 0.05, PriceScale::Linear),
+            (47.5, 52.5)
+        );
+        assert_eq!(
+            pad_prices(50.0, 50.0, 0.0, PriceScale::Linear),
+            (49.99, 50.01)
+        );
+        assert_eq!(
+            pad_prices(0.0, 0.0, 0.05, PriceScale::Linear),
+            (-0.01, 0.01)
+        );
+        let (min_price, max_price) = pad_prices(50.0, 50.0, 0.05, PriceScale::Log);
+        assert!(min_price < 50.0 && max_price > 50.0);
+        assert_eq!(
+            pad_prices(40.0, 60.0, 0.05, PriceScale::Linear),
+            (39.0, 61.0)
+        );
+    }
+
+    #[test]
+    fn flat_and_single_bar_prices_are_labelled() {
+        let style = ChartStyle::default();
+        for closes in [&[50.0; 4][..], &[50.0]] {
+            let svg = render_prices(&report(closes), &[], &style);
+            assert!(labels(&svg).contains(&50.0), "{:?}", labels(&svg));
+        }
+    }
+
+    #[test]
+    fn log_scale_leaves_out_bands_below_zero() {
+        let style = ChartStyle {
+            price_scale: PriceScale::Log,
+            ..ChartStyle::default()
+        };
+        let closes = [10.0, 4.0, 7.6, 3.0, 5.7, 2.3, 4.4, 1.7];
+        let bands = Indicator::Bollinger {
+            period: 3,
+            deviations: 3.0,
+        };
+        assert!(bands
+            .columns(&report(&closes).raw_quotes())
+            .iter()
+            .flat_map(|(_, values)| values.iter().flatten())
+            .any(|value| *value < 0.0));
+
+        let svg = render_prices(&report(&closes), &[bands], &style);
+        assert!(labels(&svg)
+            .iter()
+            .any(|label| *label > 0.0 && *label < 10.0));
+    }
+}
@@ -0,0 +1,202 @@
+use std::{collections::HashMap, path::Path};
+
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::Deserialize;
+use time::OffsetDateTime;
+use yahoo_finance_api::Quote;
+
+use crate::timeframe::{date_to_time, Interval};
+
+/// Shares of a stock bought on one date, as read from the portfolio file
+#[derive(Clone, Debug, Deserialize)]
+pub struct Lot {
+    pub ticker: String,
+    pub quantity: f64,
+    /// Price paid per share
+    pub cost_basis: f64,
+    pub purchase_date: NaiveDate,
+}
+
+/// The lots held, in the order of the portfolio file
+#[derive(Clone, Debug)]
+pub struct Portfolio {
+    pub lots: Vec<Lot>,
+    /// Start of the quotes needed to value the portfolio since its first purchase
+    pub start_date: OffsetDateTime,
+}
+
+impl Portfolio {
+    /// Reads a CSV file with the header `ticker,quantity,cost_basis,purchase_date`
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let lots = csv::Reader::from_path(path)?
+            .deserialize()
+            .collect::<Result<Vec<Lot>, _>>()?;
+        let first_purchase = match lots.iter().map(|lot| lot.purchase_date).min() {
+            Some(first_purchase) => first_purchase,
+            None => return Err("The portfolio has no positions".into()),
+        };
+        Ok(Portfolio {
+            lots,
+            start_date: date_to_time(first_purchase, false)?,
+        })
+    }
+
+    /// Tickers of the portfolio, without duplicates
+    pub fn tickers(&self) -> Vec<String> {
+        let mut tickers: Vec<String> = Vec::new();
+        for lot in &self.lots {
+            if !tickers.contains(&lot.ticker) {
+                tickers.push(lot.ticker.clone());
+            }
+        }
+        tickers
+    }
+}
+
+/// All the lots of one stock, valued at its latest close
+pub struct Holding {
+    pub ticker: String,
+    pub quantity: f64,
+    pub cost: f64,
+    pub price: f64,
+    pub previous_close: f64,
+}
+
+impl Holding {
+    pub fn value(&self) -> f64 {
+        self.quantity * self.price
+    }
+
+    pub fn unrealized_pnl(&self) -> f64 {
+        self.value() - self.cost
+    }
+
+    pub fn daily_change(&self) -> f64 {
+        self.quantity * (self.price - self.previous_close)
+    }
+}
+
+/// Holdings and value over time of a portfolio
+pub struct PortfolioReport {
+    pub holdings: Vec<Holding>,
+    /// Market value and cost of the lots held at every bar
+    pub values: Vec<(NaiveDateTime, f64, f64)>,
+}
+
+impl PortfolioReport {
+    /// Values the portfolio with the daily quotes of every ticker since the first purchase.
+    /// Returns None if a ticker has no quotes.
+    pub fn new(portfolio: &Portfolio, histories: &HashMap<String, Vec<Quote>>) -> Option<Self> {
+        let interval = Interval::OneDay;
+        let holdings = portfolio
+            .tickers()
+            .into_iter()
+            .map(|ticker| {
+                let quotes = histories.get(&ticker).filter(|q| !q.is_empty())?;
+                let lots = portfolio.lots.iter().filter(|lot| lot.ticker == ticker);
+                let price = quotes[quotes.len() - 1].close;
+                Some(Holding {
+                    quantity: lots.clone().map(|lot| lot.quantity).sum(),
+                    cost: lots.map(|lot| lot.quantity * lot.cost_basis).sum(),
+                    price,
+                    previous_close: quotes.iter().rev().nth(1).map_or(price, |q| q.close),
+                    ticker,
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        // Value every lot bought by each bar at the latest close up to that bar, or at its
+        // cost while there are no quotes yet
+        let mut times: Vec<NaiveDateTime> = histories
+            .values()
+            .flatten()
+            .map(|quote| interval.bar_time(quote.timestamp as i64))
+            .collect();
+        times.sort();
+        times.dedup();
+        let values = times
+            .into_iter()
+            .map(|time| {
+                let held = portfolio
+                    .lots
+                    .iter()
+                    .filter(|lot| lot.purchase_date <= time.date());
+                let value = held
+                    .clone()
+                    .map(|lot| {
+                        let quotes = &histories[&lot.ticker];
+                        let bars = quotes.partition_point(|quote| {
+                            interval.bar_time(quote.timestamp as i64) <= time
+                        });
+                        let price = bars
+                            .checked_sub(1)
+                            .map_or(lot.cost_basis, |i| quotes[i].close);
+                        lot.quantity * price
+                    })
+                    .sum();
+                let cost = held.map(|lot| lot.quantity * lot.cost_basis).sum();
+                (time, value, cost)
+            })
+            .collect();
+
+        Some(PortfolioReport { holdings, values })
+    }
+
+    pub fn print(&self) {
+        let total_value: f64 = self.holdings.iter().map(Holding::value).sum();
+        let total_cost: f64 = self.holdings.iter().map(|h| h.cost).sum();
+        let total_change: f64 = self.holdings.iter().map(Holding::daily_change).sum();
+
+        println!("Portfolio:");
+        println!(
+            "{:<8} {:>10} {:>10} {:>12} {:>12} {:>12} {:>8} {:>10} {:>8} {:>7}",
+            "Ticker",
+            "Quantity",
+            "Price",
+            "Value",
+            "Cost",
+            "P&L",
+            "P&L %",
+            "Day",
+            "Day %",
+            "Weight"
+        );
+        for holding in &self.holdings {
+            let previous_value = holding.value() - holding.daily_change();
+            println!(
+                "{:<8} {:>10.2} {:>10.2} {:>12.2} {:>12.2} {:>12.2} {:>7.2}% {:>10.2} {:>7.2}% {:>6.1}%",
+                holding.ticker,
+                holding.quantity,
+                holding.price,
+                holding.value(),
+                holding.cost,
+                holding.unrealized_pnl(),
+                percent(holding.unrealized_pnl(), holding.cost),
+                holding.daily_change(),
+                percent(holding.daily_change(), previous_value),
+                percent(holding.value(), total_value),
+            );
+        }
+        println!(
+            "{:<8} {:>10} {:>10} {:>12.2} {:>12.2} {:>12.2} {:>7.2}% {:>10.2} {:>7.2}% {:>6.1}%",
+            "Total",
+            "",
+            "",
+            total_value,
+            total_cost,
+            total_value - total_cost,
+            percent(total_value - total_cost, total_cost),
+            total_change,
+            percent(total_change, total_value - total_change),
+            100.0,
+        );
+    }
+}
+
+fn percent(part: f64, whole: f64) -> f64 {
+    if whole == 0.0 {
+        0.0
+    } else {
+        100.0 * part / whole
+    }
+}
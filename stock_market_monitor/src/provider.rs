@@ -0,0 +1,313 @@
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    thread,
+};
+
+use time::OffsetDateTime;
+use yahoo_finance_api::{Quote, YahooConnector};
+
+use crate::timeframe::Interval;
+
+/// Error type shared by all quote providers
+pub type ProviderError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A source of stock quotes, ex. Yahoo Finance or a local fixture
+pub trait QuoteProvider: Send + Sync {
+    /// Returns the quotes of the stock between the start and end date, one per interval
+    fn get_quote_history(
+        &self,
+        stock_name: &str,
+        start_date: OffsetDateTime,
+        end_date: OffsetDateTime,
+        interval: Interval,
+    ) -> Result<Vec<Quote>, ProviderError>;
+
+    /// Returns the most recent quote of the stock
+    fn get_latest_quote(&self, stock_name: &str) -> Result<Quote, ProviderError>;
+
+    /// Checks if the provider knows about the stock symbol
+    fn is_valid_stock(&self, stock_name: &str) -> bool {
+        self.get_latest_quote(stock_name).is_ok()
+    }
+}
+
+/// Runs `fetch` for every ticker concurrently, returning the results in the order of the tickers
+/// or the first error
+pub fn fetch_concurrently<T: Send, E: Send>(
+    tickers: &[String],
+    fetch: impl Fn(&str) -> Result<T, E> + Sync,
+) -> Result<Vec<T>, E> {
+    thread::scope(|scope| {
+        let handles: Vec<_> = tickers
+            .iter()
+            .map(|ticker| scope.spawn(|| fetch(ticker)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    })
+}
+
+/// Fetches quotes from Yahoo Finance
+pub struct YahooProvider {
+    connector: YahooConnector,
+}
+
+impl YahooProvider {
+    pub fn new() -> Result<Self, ProviderError> {
+        Ok(YahooProvider {
+            connector: YahooConnector::new()?,
+        })
+    }
+}
+
+impl QuoteProvider for YahooProvider {
+    fn get_quote_history(
+        &self,
+        stock_name: &str,
+        start_date: OffsetDateTime,
+        end_date: OffsetDateTime,
+        interval: Interval,
+    ) -> Result<Vec<Quote>, ProviderError> {
+        // returns historic quotes with the requested interval
+        let resp = tokio_test::block_on(self.connector.get_quote_history_interval(
+            stock_name,
+            start_date,
+            end_date,
+            interval.as_str(),
+        ))?;
+        Ok(resp.quotes()?)
+    }
+
+    fn get_latest_quote(&self, stock_name: &str) -> Result<Quote, ProviderError> {
+        let resp = tokio_test::block_on(self.connector.get_latest_quotes(stock_name, "1d"))?;
+        Ok(resp.last_quote()?)
+    }
+}
+
+/// Reads quotes from a local CSV or JSON file, or from a directory holding
+/// one `<TICKER>.csv` / `<TICKER>.json` file per stock. The quotes are used
+/// at whatever interval they were recorded with.
+pub struct FileProvider {
+    path: PathBuf,
+}
+
+impl FileProvider {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileProvider { path: path.into() }
+    }
+
+    fn quote_file(&self, stock_name: &str) -> Result<PathBuf, ProviderError> {
+        if !self.path.is_dir() {
+            return Ok(self.path.clone());
+        }
+        ["csv", "json"]
+            .iter()
+            .map(|extension| self.path.join(format!("{}.{}", stock_name, extension)))
+            .find(|file| file.is_file())
+            .ok_or_else(|| {
+                format!("No data file for {} in {}", stock_name, self.path.display()).into()
+            })
+    }
+
+    fn read_quotes(&self, stock_name: &str) -> Result<Vec<Quote>, ProviderError> {
+        let file = self.quote_file(stock_name)?;
+        let mut quotes: Vec<Quote> = match file.extension().and_then(|e| e.to_str()) {
+            Some("csv") => csv::Reader::from_path(&file)?
+                .deserialize()
+                .collect::<Result<_, _>>()?,
+            Some("json") => serde_json::from_reader(BufReader::new(File::open(&file)?))?,
+            _ => {
+                return Err(format!(
+                    "Unsupported data file {}, expected .csv or .json",
+                    file.display()
+                )
+                .into())
+            }
+        };
+        quotes.sort_by_key(|quote| quote.timestamp);
+        Ok(quotes)
+    }
+}
+
+impl QuoteProvider for FileProvider {
+    fn get_quote_history(
+        &self,
+        stock_name: &str,
+        start_date: OffsetDateTime,
+        end_date: OffsetDateTime,
+        _interval: Interval,
+    ) -> Result<Vec<Quote>, ProviderError> {
+        let (start, end) = (start_date.unix_timestamp(), end_date.unix_timestamp());
+        Ok(self
+            .read_quotes(stock_name)?
+            .into_iter()
+            .filter(|quote| (start..=end).contains(&(quote.timestamp as i64)))
+            .collect())
+    }
+
+    fn get_latest_quote(&self, stock_name: &str) -> Result<Quote, ProviderError> {
+        self.read_quotes(stock_name)?
+            .pop()
+            .ok_or_else(|| format!("No quotes found for {}", stock_name).into())
+    }
+}
+
+/// Writes quotes to a CSV file in the format read by the FileProvider
+pub fn write_quotes(path: &Path, quotes: &[Quote]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut writer = csv::Writer::from_path(path)?;
+    for quote in quotes {
+        writer.serialize(quote)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use time::macros::datetime;
+
+    use super::*;
+    use crate::{
+        monitor::Monitor,
+        report::StockReport,
+        volatility::{Volatility, VolatilityMeasure},
+    };
+
+    /// Serves fixed quotes from memory, so the pipeline runs without the network or files
+    struct FixtureProvider {
+        quotes: HashMap<String, Vec<Quote>>,
+    }
+
+    impl QuoteProvider for FixtureProvider {
+        fn get_quote_history(
+            &self,
+            stock_name: &str,
+            start_date: OffsetDateTime,
+            end_date: OffsetDateTime,
+            _interval: Interval,
+        ) -> Result<Vec<Quote>, ProviderError> {
+            let (start, end) = (start_date.unix_timestamp(), end_date.unix_timestamp());
+            Ok(self
+                .quotes
+                .get(stock_name)
+                .ok_or_else(|| format!("Unknown stock {}", stock_name))?
+                .iter()
+                .filter(|quote| (start..=end).contains(&(quote.timestamp as i64)))
+                .cloned()
+                .collect())
+        }
+
+        fn get_latest_quote(&self, stock_name: &str) -> Result<Quote, ProviderError> {
+            self.quotes
+                .get(stock_name)
+                .and_then(|quotes| quotes.last().cloned())
+                .ok_or_else(|| format!("No quotes found for {}", stock_name).into())
+        }
+    }
+
+    /// Midnight UTC of 2024-03-01
+    const FIRST_DAY: u64 = 1_709_251_200;
+
+    fn quote(day: u64, low: f64, high: f64, close: f64) -> Quote {
+        Quote {
+            timestamp: FIRST_DAY + day * 86_400,
+            open: close,
+            high,
+            low,
+            volume: 1000,
+            close,
+            adjclose: close,
+        }
+    }
+
+    fn monitor() -> Monitor {
+        let quotes = vec![
+            quote(0, 99.0, 101.0, 100.0),
+            // 10% range, the only volatile day at a 2% threshold
+            quote(1, 95.0, 105.5, 104.0),
+            quote(2, 96.0, 97.5, 97.0),
+            quote(3, 101.0, 102.0, 101.5),
+        ];
+        Monitor {
+            provider: Box::new(FixtureProvider {
+                quotes: HashMap::from([("TEST".to_owned(), quotes)]),
+            }),
+            interval: Interval::OneDay,
+            volatility: Volatility::new(VolatilityMeasure::Range, None, 20),
+            indicators: Vec::new(),
+            anchor_to_data: true,
+        }
+    }
+
+    #[test]
+    fn report_finds_min_max_close_and_volatile_days() {
+        let monitor = monitor();
+        let report = monitor
+            .report(
+                "TEST",
+                datetime!(2024-03-01 0:00 UTC),
+                datetime!(2024-03-31 0:00 UTC),
+                Interval::OneDay,
+            )
+            .unwrap();
+
+        assert_eq!(report.quotes.len(), 4);
+        assert_eq!(report.min_close.1, 97.0);
+        assert_eq!(report.min_close.0.to_string(), "2024-03-03 00:00:00");
+        assert_eq!(report.max_close.1, 104.0);
+        assert_eq!(report.max_close.0.to_string(), "2024-03-02 00:00:00");
+        let volatile_days: Vec<String> = report
+            .volatile_days
+            .iter()
+            .map(|(time, _)| time.date().to_string())
+            .collect();
+        assert_eq!(volatile_days, ["2024-03-02"]);
+    }
+
+    #[test]
+    fn report_of_a_window_without_quotes_is_an_error() {
+        let monitor = monitor();
+        let error = monitor
+            .report(
+                "TEST",
+                datetime!(2023-01-01 0:00 UTC),
+                datetime!(2023-01-31 0:00 UTC),
+                Interval::OneDay,
+            )
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "No quotes found for TEST in the selected range"
+        );
+        assert!(monitor
+            .report(
+                "NOPE",
+                datetime!(2024-03-01 0:00 UTC),
+                datetime!(2024-03-31 0:00 UTC),
+                Interval::OneDay,
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn stock_report_needs_quotes() {
+        let volatility = Volatility::new(VolatilityMeasure::Range, None, 20);
+        assert!(StockReport::new("TEST", &[], Interval::OneDay, &volatility).is_none());
+    }
+
+    #[test]
+    fn today_is_the_latest_quote_when_anchored_to_data() {
+        let monitor = monitor();
+        assert_eq!(
+            monitor.today(&["TEST".to_owned()]),
+            datetime!(2024-03-04 0:00 UTC)
+        );
+    }
+}
@@ -0,0 +1,111 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use time::OffsetDateTime;
+
+use crate::{api, notify::Notification, server::Dashboard, timeframe};
+
+/// Polls the latest quotes of the watched stocks and pushes their updated stats to the
+/// browsers listening on `/events`
+pub struct Refresher {
+    /// Time between two polls, or None to never poll
+    every: Option<Duration>,
+    /// Whether to skip polling while the US stock market is closed
+    market_hours_only: bool,
+    /// One channel per connected browser
+    clients: Mutex<Vec<Sender<String>>>,
+}
+
+impl Refresher {
+    pub fn new(every: Option<Duration>, market_hours_only: bool) -> Self {
+        Refresher {
+            every,
+            market_hours_only,
+            clients: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Returns the channel on which a browser receives the Server-Sent Events
+    pub fn subscribe(&self) -> Receiver<String> {
+        let (sender, receiver) = mpsc::channel();
+        self.clients.lock().unwrap().push(sender);
+        receiver
+    }
+
+    /// Sends an event to every browser, forgetting the ones that disconnected
+    fn broadcast(&self, event: &str, data: &str) {
+        let message = format!("event: {}\ndata: {}\n\n", event, data);
+        self.clients
+            .lock()
+            .unwrap()
+            .retain(|client| client.send(message.clone()).is_ok());
+    }
+
+    /// Polls until `running` is cleared, sending an `update` event with the stats of a
+    /// stock whenever its latest quote changes, notifying its latest bar when volatile and
+    /// checking the alert rules
+    pub fn run(&self, dashboard: &Dashboard, running: &AtomicBool) {
+        let Some(every) = self.every else {
+            return;
+        };
+        let mut latest_quotes = HashMap::new();
+        let mut next_poll = Instant::now() + every;
+
+        while running.load(Ordering::SeqCst) {
+            if Instant::now() < next_poll {
+                thread::sleep(Duration::from_millis(200));
+                continue;
+            }
+            next_poll = Instant::now() + every;
+            if self.market_hours_only && !timeframe::is_market_open(OffsetDateTime::now_utc()) {
+                continue;
+            }
+
+            for stock_name in &dashboard.stock_names {
+                let quote = match dashboard.monitor.provider.get_latest_quote(stock_name) {
+                    Ok(quote) => quote,
+                    Err(error) => {
+                        eprintln!("Could not refresh {}: {}", stock_name, error);
+                        continue;
+                    }
+                };
+                let key = (quote.timestamp, quote.close.to_bits(), quote.volume);
+                if latest_quotes.insert(stock_name.clone(), key) == Some(key) {
+                    continue;
+                }
+
+                let (window, interval) = (dashboard.window, dashboard.monitor.interval);
+                let requested = api::fetch_report(stock_name, window, interval, dashboard)
+                    .and_then(|requested| {
+                        let stats = api::respond("stats", &requested, &dashboard.monitor)?;
+                        Ok((requested, stats))
+                    });
+                match requested {
+                    Ok((requested, stats)) => {
+                        println!("Refreshed {}: ${:.2}", stock_name, quote.close);
+                        self.broadcast("update", &stats);
+                        let volatile = Notification::volatile(
+                            &requested.report,
+                            &dashboard.monitor.volatility,
+                        );
+                        if let (Some(notifier), Some(volatile)) = (&dashboard.notifier, volatile) {
+                            notifier.notify(&volatile);
+                        }
+                    }
+                    Err(error) => eprintln!("Could not refresh {}: {}", stock_name, error.message),
+                }
+            }
+            if let Some(alerts) = &dashboard.alerts {
+                alerts.check(&dashboard.monitor);
+            }
+        }
+    }
+}
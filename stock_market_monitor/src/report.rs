@@ -0,0 +1,106 @@
+use chrono::NaiveDateTime;
+use yahoo_finance_api::Quote;
+
+use crate::{indicators::Indicator, timeframe::Interval, volatility::Volatility};
+
+/// Bar data, volatile bars and min/max close derived from one stock's quotes
+pub struct StockReport {
+    pub stock_name: String,
+    pub interval: Interval,
+    pub quotes: Vec<(NaiveDateTime, Quote)>,
+    pub series: Vec<(NaiveDateTime, f64)>,
+    pub volatile_days: Vec<(NaiveDateTime, Quote)>,
+    pub min_close: (NaiveDateTime, f64),
+    pub max_close: (NaiveDateTime, f64),
+}
+
+impl StockReport {
+    /// Returns None if there are no quotes to report on
+    pub fn new(
+        stock_name: &str,
+        quotes: &[Quote],
+        interval: Interval,
+        volatility: &Volatility,
+    ) -> Option<StockReport> {
+        //This iterator is created to help derive the bar data, volatile days and min/max close
+        let date_quote_pairs = quotes
+            .iter()
+            .map(|quote| (interval.bar_time(quote.timestamp as i64), quote));
+
+        // Derive closing prices
+        let series: Vec<(NaiveDateTime, f64)> = date_quote_pairs
+            .clone()
+            .map(|(quote_date, quote)| (quote_date, quote.close))
+            .collect();
+
+        // Derive volatility data
+        let volatile_days: Vec<(NaiveDateTime, Quote)> = date_quote_pairs
+            .clone()
+            .zip(volatility.flags(quotes))
+            .filter(|(_, volatile)| *volatile)
+            .map(|(pair, _)| pair)
+            .map(|(quote_date, quote)| (quote_date, quote.clone()))
+            .collect();
+
+        // Derive min closing prices over the period
+        let min_close = date_quote_pairs
+            .clone()
+            .min_by(|(_, quote1), (_, quote2)| quote1.close.partial_cmp(&quote2.close).unwrap())
+            .map(|(date, quote)| (date, quote.close))?;
+
+        // Derive max closing prices over the period
+        let max_close = date_quote_pairs
+            .clone()
+            .max_by(|(_, quote1), (_, quote2)| quote1.close.partial_cmp(&quote2.close).unwrap())
+            .map(|(date, quote)| (date, quote.close))?;
+
+        Some(StockReport {
+            stock_name: stock_name.to_owned(),
+            interval,
+            quotes: date_quote_pairs
+                .map(|(quote_date, quote)| (quote_date, quote.clone()))
+                .collect(),
+            series,
+            volatile_days,
+            min_close,
+            max_close,
+        })
+    }
+
+    /// The quotes without their bar times, as used by the indicators
+    pub fn raw_quotes(&self) -> Vec<Quote> {
+        self.quotes.iter().map(|(_, quote)| quote.clone()).collect()
+    }
+
+    pub fn print_stats(&self, indicators: &[Indicator]) {
+        print!("{}", self.stats(indicators));
+    }
+
+    /// The max/min close, volatile bars and latest indicator values, one per line
+    pub fn stats(&self, indicators: &[Indicator]) -> String {
+        let time_format = self.interval.time_format();
+        let mut stats = format!(
+            "{} Stats:\nMax Closing Price: ${:.2} on {}\nMin Closing Price: ${:.2} on {}\n",
+            self.stock_name,
+            self.max_close.1,
+            self.max_close.0.format(time_format),
+            self.min_close.1,
+            self.min_close.0.format(time_format)
+        );
+
+        // Output how many bars were volatile
+        stats += &format!(
+            "Volatile Days: {} of {} ({:.1}%)\n",
+            self.volatile_days.len(),
+            self.quotes.len(),
+            100.0 * self.volatile_days.len() as f64 / self.quotes.len() as f64
+        );
+
+        // Output the latest value of every selected indicator
+        let quotes = self.raw_quotes();
+        for indicator in indicators {
+            stats += &format!("{}: {}\n", indicator, indicator.latest(&quotes));
+        }
+        stats
+    }
+}
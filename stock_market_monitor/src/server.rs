@@ -0,0 +1,220 @@
+use std::{
+    io::{Cursor, Write},
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::RecvTimeoutError,
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use tiny_http::{Header, Method, Request, Response, Server, StatusCode};
+
+use crate::{
+    alerts::Alerts,
+    api,
+    backtest::Backtest,
+    charts,
+    monitor::Monitor,
+    notify::Notifier,
+    plot::{ChartKind, ChartStyle},
+    portfolio::Portfolio,
+    refresh::Refresher,
+    timeframe::Window,
+};
+
+/// Number of threads answering requests, so a slow client does not block the others
+const WORKER_COUNT: usize = 4;
+
+/// Time after which an idle event stream sends a comment, so that closed browsers are noticed
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// What the dashboard serves
+pub struct Dashboard {
+    /// Tickers with generated charts, or "comparison" for the overlaid chart
+    pub chart_names: Vec<String>,
+    /// Chart shown first on the page
+    pub chart_kind: ChartKind,
+    /// Size and colors of the rendered charts
+    pub style: ChartStyle,
+    /// Tickers selected on the command line, compared by default on the comparison chart
+    pub stock_names: Vec<String>,
+    /// Fetches and analyzes the stocks requested through the API
+    pub monitor: Monitor,
+    /// Dates used when a request does not specify any
+    pub window: Window,
+    /// Rules checked on every refresh
+    pub alerts: Option<Alerts>,
+    /// Strategy whose equity is charted at `/chart/equity/{ticker}.png`
+    pub backtest: Option<Backtest>,
+    /// Ticker the stocks are compared against at `/chart/benchmark/{ticker}.png`
+    pub benchmark: Option<String>,
+    /// Positions charted at `/chart/portfolio.png`
+    pub portfolio: Option<Portfolio>,
+    /// Sinks of the volatile bars found on refresh
+    pub notifier: Option<Notifier>,
+    /// Polls the stocks and pushes their updates to the page
+    pub refresher: Refresher,
+}
+
+type HttpResponse = Response<Cursor<Vec<u8>>>;
+
+/// Serves the dashboard until Ctrl+C is pressed
+pub fn serve(address: &str, dashboard: Dashboard) -> Result<(), Box<dyn std::error::Error>> {
+    let server = Arc::new(Server::http(address).map_err(|e| e.to_string())?);
+    let dashboard = Arc::new(dashboard);
+    println!(
+        "Open http://{} in your browser. \nPress Control + C to stop.",
+        address
+    );
+
+    // Stop accepting requests on Ctrl+C, requests in progress are completed first
+    let running = Arc::new(AtomicBool::new(true));
+    let handler_running = running.clone();
+    ctrlc::set_handler(move || handler_running.store(false, Ordering::SeqCst))?;
+
+    let workers: Vec<_> = (0..WORKER_COUNT)
+        .map(|_| {
+            let server = server.clone();
+            let dashboard = dashboard.clone();
+            let running = running.clone();
+            thread::spawn(move || {
+                while running.load(Ordering::SeqCst) {
+                    match server.recv_timeout(Duration::from_millis(200)) {
+                        // Event streams stay open, so they get their own thread to keep
+                        // the workers free
+                        Ok(Some(request))
+                            if request.method() == &Method::Get
+                                && request.url().split('?').next() == Some("/events") =>
+                        {
+                            let dashboard = dashboard.clone();
+                            let running = running.clone();
+                            thread::spawn(move || stream_events(request, &dashboard, &running));
+                        }
+                        Ok(Some(request)) => handle_request(request, &dashboard),
+                        Ok(None) => {}
+                        Err(error) => eprintln!("Problem Receiving Request: {}", error),
+                    }
+                }
+            })
+        })
+        .collect();
+    let refresher = {
+        let dashboard = dashboard.clone();
+        let running = running.clone();
+        thread::spawn(move || dashboard.refresher.run(&dashboard, &running))
+    };
+    for worker in workers {
+        let _ = worker.join();
+    }
+    let _ = refresher.join();
+    println!("Server stopped.");
+    Ok(())
+}
+
+fn handle_request(request: Request, dashboard: &Dashboard) {
+    let response = match request.method() {
+        // A bug in one request must not take the worker down with it
+        Method::Get | Method::Head => {
+            panic::catch_unwind(AssertUnwindSafe(|| route(request.url(), dashboard)))
+                .unwrap_or_else(|_| text_response(500, "Internal Server Error"))
+        }
+        _ => text_response(405, "Method Not Allowed").with_header(header("Allow", "GET, HEAD")),
+    };
+    // A failed response only affects that client, ex. when the browser closed the connection
+    if let Err(error) = request.respond(response) {
+        eprintln!("Problem Handling Request: {}", error);
+    }
+}
+
+/// Streams the refresh updates to a browser as Server-Sent Events until it disconnects
+fn stream_events(request: Request, dashboard: &Dashboard, running: &AtomicBool) {
+    let updates = dashboard.refresher.subscribe();
+    let mut writer = request.into_writer();
+    let head =
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\r\n";
+    let mut last_write = Instant::now();
+    let mut message = head.to_owned();
+
+    while running.load(Ordering::SeqCst) {
+        if !message.is_empty() {
+            if writer
+                .write_all(message.as_bytes())
+                .and_then(|_| writer.flush())
+                .is_err()
+            {
+                return;
+            }
+            last_write = Instant::now();
+        }
+        message = match updates.recv_timeout(Duration::from_millis(200)) {
+            Ok(update) => update,
+            Err(RecvTimeoutError::Timeout) if last_write.elapsed() >= KEEP_ALIVE => {
+                ": keep-alive\n\n".to_owned()
+            }
+            Err(RecvTimeoutError::Timeout) => String::new(),
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+    }
+}
+
+/// Finds the response for the requested path
+fn route(url: &str, dashboard: &Dashboard) -> HttpResponse {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+
+    if let Some(endpoint) = path.strip_prefix("/api/") {
+        return match api::handle(endpoint, query, dashboard) {
+            Ok(json) => {
+                Response::from_string(json).with_header(header("Content-Type", "application/json"))
+            }
+            Err(error) => Response::from_string(error.to_json())
+                .with_status_code(StatusCode(error.status))
+                .with_header(header("Content-Type", "application/json")),
+        };
+    }
+
+    if path == "/" {
+        // Serve the html file
+        return Response::from_string(render_page(dashboard))
+            .with_header(header("Content-Type", "text/html; charset=utf-8"));
+    }
+
+    if let Some(endpoint) = path.strip_prefix("/chart/") {
+        // Render the requested chart
+        return match charts::handle(endpoint, query, dashboard) {
+            Ok((format, chart)) => Response::from_data(chart)
+                .with_header(header("Content-Type", format.content_type())),
+            Err(error) => text_response(error.status, &error.message),
+        };
+    }
+
+    text_response(404, "Not Found")
+}
+
+/// Fills the dashboard page with the names of the generated charts, the chart kind shown first and
+/// the size of the charts
+fn render_page(dashboard: &Dashboard) -> String {
+    include_str!("plots.html")
+        .replace(
+            "{{charts}}",
+            &serde_json::to_string(&dashboard.chart_names).unwrap(),
+        )
+        .replace(
+            "{{candlestick}}",
+            &(dashboard.chart_kind == ChartKind::Candlestick).to_string(),
+        )
+        .replace("{{width}}", &dashboard.style.width.to_string())
+        .replace("{{height}}", &dashboard.style.height.to_string())
+}
+
+fn text_response(status: u16, text: &str) -> HttpResponse {
+    Response::from_string(text)
+        .with_status_code(StatusCode(status))
+        .with_header(header("Content-Type", "text/plain; charset=utf-8"))
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap()
+}
@@ -0,0 +1,319 @@
+use serde::Serialize;
+
+use crate::report::StockReport;
+
+/// Confidence level of the value at risk
+const VAR_CONFIDENCE: f64 = 0.95;
+
+/// Simple returns between consecutive values
+pub fn returns(values: &[f64]) -> Vec<f64> {
+    values.windows(2).map(|w| w[1] / w[0] - 1.0).collect()
+}
+
+/// Logarithmic returns between consecutive values
+pub fn log_returns(values: &[f64]) -> Vec<f64> {
+    values.windows(2).map(|w| (w[1] / w[0]).ln()).collect()
+}
+
+pub fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len().max(1) as f64
+}
+
+/// Sample standard deviation
+pub fn standard_deviation(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let mean = mean(values);
+    (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (values.len() - 1) as f64).sqrt()
+}
+
+/// Sample covariance of two series of the same length
+pub fn covariance(a: &[f64], b: &[f64]) -> f64 {
+    if a.len() < 2 {
+        return 0.0;
+    }
+    let (mean_a, mean_b) = (mean(a), mean(b));
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| (a - mean_a) * (b - mean_b))
+        .sum::<f64>()
+        / (a.len() - 1) as f64
+}
+
+/// Pearson correlation of two series of the same length, from -1 to 1
+pub fn correlation(a: &[f64], b: &[f64]) -> f64 {
+    let deviations = standard_deviation(a) * standard_deviation(b);
+    if deviations == 0.0 {
+        return 0.0;
+    }
+    covariance(a, b) / deviations
+}
+
+/// Annualized mean return over the standard deviation, without a risk free rate
+pub fn sharpe_ratio(returns: &[f64], bars_per_year: f64) -> f64 {
+    let deviation = standard_deviation(returns);
+    if deviation == 0.0 {
+        return 0.0;
+    }
+    mean(returns) / deviation * bars_per_year.sqrt()
+}
+
+/// Like the Sharpe ratio but only penalizing the returns below zero
+pub fn sortino_ratio(returns: &[f64], bars_per_year: f64) -> f64 {
+    let downside = (returns.iter().map(|r| r.min(0.0).powi(2)).sum::<f64>()
+        / returns.len().max(1) as f64)
+        .sqrt();
+    if downside == 0.0 {
+        return 0.0;
+    }
+    mean(returns) / downside * bars_per_year.sqrt()
+}
+
+/// Largest fall from a peak, with the indices of the peak and of the trough
+pub struct Drawdown {
+    pub depth: f64,
+    pub peak: usize,
+    pub trough: usize,
+}
+
+pub fn max_drawdown(values: &[f64]) -> Drawdown {
+    let mut drawdown = Drawdown {
+        depth: 0.0,
+        peak: 0,
+        trough: 0,
+    };
+    let mut peak = 0;
+    for (i, value) in values.iter().enumerate() {
+        if *value > values[peak] {
+            peak = i;
+        }
+        let depth = 1.0 - value / values[peak];
+        if depth > drawdown.depth {
+            drawdown = Drawdown {
+                depth,
+                peak,
+                trough: i,
+            };
+        }
+    }
+    drawdown
+}
+
+/// Historical value at risk and conditional value at risk (expected shortfall) of the
+/// returns at the given confidence, as positive losses
+pub fn value_at_risk(returns: &[f64], confidence: f64) -> (f64, f64) {
+    if returns.is_empty() {
+        return (0.0, 0.0);
+    }
+    // NaN returns, ex. after a zero close, are sorted last rather than panicking
+    let mut sorted = returns.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    // 1 - 0.95 is slightly above 0.05, which would put one return too many in the tail
+    let tail = (((1.0 - confidence) * sorted.len() as f64 - 1e-9).ceil() as usize).max(1);
+    (-sorted[tail - 1], -mean(&sorted[..tail]))
+}
+
+/// Asymmetry of the returns, negative when large losses are more frequent than large gains
+pub fn skewness(values: &[f64]) -> f64 {
+    let (mean, deviation) = (mean(values), population_deviation(values));
+    if deviation == 0.0 {
+        return 0.0;
+    }
+    values
+        .iter()
+        .map(|v| ((v - mean) / deviation).powi(3))
+        .sum::<f64>()
+        / values.len() as f64
+}
+
+/// Excess kurtosis of the returns, positive when extreme returns are more frequent than
+/// in a normal distribution
+pub fn kurtosis(values: &[f64]) -> f64 {
+    let (mean, deviation) = (mean(values), population_deviation(values));
+    if deviation == 0.0 {
+        return 0.0;
+    }
+    values
+        .iter()
+        .map(|v| ((v - mean) / deviation).powi(4))
+        .sum::<f64>()
+        / values.len() as f64
+        - 3.0
+}
+
+fn population_deviation(values: &[f64]) -> f64 {
+    let mean = mean(values);
+    (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len().max(1) as f64).sqrt()
+}
+
+/// Risk and return of a stock's closes over the selected window, in percent where relevant
+#[derive(Serialize)]
+pub struct Statistics {
+    pub ticker: String,
+    pub bars: usize,
+    pub total_return: f64,
+    pub mean_return: f64,
+    pub mean_log_return: f64,
+    pub annualized_volatility: f64,
+    pub max_drawdown: f64,
+    pub drawdown_peak: String,
+    pub drawdown_trough: String,
+    pub sharpe_ratio: f64,
+    pub sortino_ratio: f64,
+    pub value_at_risk: f64,
+    pub conditional_value_at_risk: f64,
+    pub skewness: f64,
+    pub kurtosis: f64,
+}
+
+impl Statistics {
+    /// Returns None without at least two returns
+    pub fn new(report: &StockReport) -> Option<Self> {
+        let closes: Vec<f64> = report.series.iter().map(|(_, close)| *close).collect();
+        if closes.len() < 3 {
+            return None;
+        }
+        let bars_per_year = report.interval.bars_per_year();
+        let returns = returns(&closes);
+        let log_returns = log_returns(&closes);
+        let drawdown = max_drawdown(&closes);
+        let (value_at_risk, conditional_value_at_risk) = value_at_risk(&returns, VAR_CONFIDENCE);
+        let format_time = |i: usize| {
+            report.series[i]
+                .0
+                .format(report.interval.time_format())
+                .to_string()
+        };
+
+        Some(Statistics {
+            ticker: report.stock_name.clone(),
+            bars: closes.len(),
+            total_return: (closes[closes.len() - 1] / closes[0] - 1.0) * 100.0,
+            mean_return: mean(&returns) * 100.0,
+            mean_log_return: mean(&log_returns) * 100.0,
+            annualized_volatility: standard_deviation(&log_returns) * bars_per_year.sqrt() * 100.0,
+            max_drawdown: drawdown.depth * 100.0,
+            drawdown_peak: format_time(drawdown.peak),
+            drawdown_trough: format_time(drawdown.trough),
+            sharpe_ratio: sharpe_ratio(&returns, bars_per_year),
+            sortino_ratio: sortino_ratio(&returns, bars_per_year),
+            value_at_risk: value_at_risk * 100.0,
+            conditional_value_at_risk: conditional_value_at_risk * 100.0,
+            skewness: skewness(&returns),
+            kurtosis: kurtosis(&returns),
+        })
+    }
+
+    pub fn print(&self) {
+        let confidence = VAR_CONFIDENCE * 100.0;
+        let rows = [
+            ("Total Return", format!("{:.2}%", self.total_return)),
+            ("Mean Return", format!("{:.3}%", self.mean_return)),
+            ("Mean Log Return", format!("{:.3}%", self.mean_log_return)),
+            (
+                "Annualized Volatility",
+                format!("{:.2}%", self.annualized_volatility),
+            ),
+            ("Max Drawdown", format!("{:.2}%", self.max_drawdown)),
+            ("Drawdown Peak", self.drawdown_peak.clone()),
+            ("Drawdown Trough", self.drawdown_trough.clone()),
+            ("Sharpe Ratio", format!("{:.2}", self.sharpe_ratio)),
+            ("Sortino Ratio", format!("{:.2}", self.sortino_ratio)),
+            (
+                &format!("VaR ({:.0}%)", confidence),
+                format!("{:.2}%", self.value_at_risk),
+            ),
+            (
+                &format!("CVaR ({:.0}%)", confidence),
+                format!("{:.2}%", self.conditional_value_at_risk),
+            ),
+            ("Skewness", format!("{:.2}", self.skewness)),
+            ("Excess Kurtosis", format!("{:.2}", self.kurtosis)),
+        ];
+        println!("{} Risk and Return ({} bars):", self.ticker, self.bars);
+        for (name, value) in rows {
+            println!("  {:<24}{:>12}", name, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(value: f64, expected: f64) {
+        assert!((value - expected).abs() < 1e-9, "{} != {}", value, expected);
+    }
+
+    #[test]
+    fn max_drawdown_finds_the_deepest_fall_from_a_peak() {
+        let drawdown = max_drawdown(&[100.0, 120.0, 90.0, 110.0, 60.0, 130.0]);
+        assert_close(drawdown.depth, 0.5);
+        assert_eq!((drawdown.peak, drawdown.trough), (1, 4));
+
+        let drawdown = max_drawdown(&[1.0, 2.0, 3.0]);
+        assert_close(drawdown.depth, 0.0);
+        assert_eq!((drawdown.peak, drawdown.trough), (0, 0));
+    }
+
+    #[test]
+    fn value_at_risk_averages_the_worst_returns() {
+        // At 95%, the tail of 20 returns is the single worst one
+        let mut returns: Vec<f64> = (1..=18).map(|i| i as f64 / 100.0).collect();
+        returns.extend([-0.04, -0.08]);
+        let (var, cvar) = value_at_risk(&returns, 0.95);
+        assert_close(var, 0.08);
+        assert_close(cvar, 0.08);
+
+        // At 90%, it is the two worst
+        let (var, cvar) = value_at_risk(&returns, 0.9);
+        assert_close(var, 0.04);
+        assert_close(cvar, 0.06);
+
+        assert_eq!(value_at_risk(&[], 0.95), (0.0, 0.0));
+    }
+
+    #[test]
+    fn value_at_risk_of_nan_returns_does_not_panic() {
+        let returns = returns(&[10.0, 0.0, 0.0, 5.0, 4.0]);
+        assert!(returns.iter().any(|r| r.is_nan()));
+        let (var, _) = value_at_risk(&returns, 0.5);
+        assert!(var > 0.0 || var.is_nan());
+    }
+
+    #[test]
+    fn sortino_ratio_only_penalizes_losses() {
+        let returns = [0.02, -0.01, 0.03, -0.02];
+        // Mean 0.005 over a downside deviation of sqrt((0.0001 + 0.0004) / 4)
+        assert_close(sortino_ratio(&returns, 1.0), 0.005 / 0.000125f64.sqrt());
+        assert_close(
+            sortino_ratio(&returns, 4.0),
+            2.0 * 0.005 / 0.000125f64.sqrt(),
+        );
+        assert_eq!(sortino_ratio(&[0.01, 0.02], 252.0), 0.0);
+    }
+
+    #[test]
+    fn skewness_and_kurtosis_of_known_values() {
+        // Deviations from the mean of 4 are -3, -2, -1 and 6, with a variance of 12.5
+        let values = [1.0, 2.0, 3.0, 10.0];
+        assert_close(skewness(&values), 180.0 / 4.0 / 12.5f64.powf(1.5));
+        assert_close(kurtosis(&values), 1394.0 / 4.0 / 12.5f64.powi(2) - 3.0);
+
+        assert_close(skewness(&[1.0, 2.0, 3.0]), 0.0);
+        // Two values of equal frequency are as far from a normal distribution as can be
+        assert_close(kurtosis(&[-1.0, 1.0, -1.0, 1.0]), -2.0);
+        assert_eq!(skewness(&[5.0; 3]), 0.0);
+        assert_eq!(kurtosis(&[5.0; 3]), 0.0);
+    }
+
+    #[test]
+    fn covariance_and_correlation_of_known_values() {
+        assert_close(covariance(&[1.0, 2.0, 3.0], &[2.0, 4.0, 7.0]), 2.5);
+        assert_close(correlation(&[1.0, 2.0, 3.0], &[3.0, 2.0, 1.0]), -1.0);
+        assert_close(correlation(&[1.0, 2.0, 3.0], &[2.0, 4.0, 6.0]), 1.0);
+        assert_eq!(correlation(&[1.0, 2.0, 3.0], &[5.0; 3]), 0.0);
+        assert_eq!(covariance(&[1.0], &[2.0]), 0.0);
+    }
+}
@@ -0,0 +1,259 @@
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Utc, Weekday};
+use clap::ValueEnum;
+use time::{Duration, OffsetDateTime};
+
+/// Bar size of the fetched quotes
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Interval {
+    #[value(name = "1m")]
+    OneMinute,
+    #[value(name = "5m")]
+    FiveMinutes,
+    #[value(name = "1h")]
+    OneHour,
+    #[value(name = "1d")]
+    OneDay,
+    #[value(name = "1wk")]
+    OneWeek,
+    #[value(name = "1mo")]
+    OneMonth,
+}
+
+impl Interval {
+    /// Number of bars in a year of trading, with 252 trading days of 6.5 hours
+    pub fn bars_per_year(&self) -> f64 {
+        match self {
+            Interval::OneMinute => 252.0 * 390.0,
+            Interval::FiveMinutes => 252.0 * 78.0,
+            Interval::OneHour => 252.0 * 6.5,
+            Interval::OneDay => 252.0,
+            Interval::OneWeek => 52.0,
+            Interval::OneMonth => 12.0,
+        }
+    }
+
+    /// Interval name understood by Yahoo Finance
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Interval::OneMinute => "1m",
+            Interval::FiveMinutes => "5m",
+            Interval::OneHour => "1h",
+            Interval::OneDay => "1d",
+            Interval::OneWeek => "1wk",
+            Interval::OneMonth => "1mo",
+        }
+    }
+
+    pub fn is_intraday(&self) -> bool {
+        matches!(
+            self,
+            Interval::OneMinute | Interval::FiveMinutes | Interval::OneHour
+        )
+    }
+
+    /// Time covered by a single bar
+    pub fn duration(&self) -> chrono::Duration {
+        match self {
+            Interval::OneMinute => chrono::Duration::minutes(1),
+            Interval::FiveMinutes => chrono::Duration::minutes(5),
+            Interval::OneHour => chrono::Duration::hours(1),
+            Interval::OneDay => chrono::Duration::days(1),
+            Interval::OneWeek => chrono::Duration::weeks(1),
+            Interval::OneMonth => chrono::Duration::days(30),
+        }
+    }
+
+    /// Format used to print the time of a bar
+    pub fn time_format(&self) -> &'static str {
+        if self.is_intraday() {
+            "%Y-%m-%d %H:%M"
+        } else {
+            "%Y-%m-%d"
+        }
+    }
+
+    /// Format used for the labels of the time axis
+    pub fn axis_format(&self) -> &'static str {
+        if self.is_intraday() {
+            "%m-%d %H:%M"
+        } else {
+            "%Y-%m-%d"
+        }
+    }
+
+    /// Converts a quote timestamp to the time plotted on the chart axis.
+    /// Bars of a day or longer are placed at midnight of their date.
+    pub fn bar_time(&self, timestamp: i64) -> NaiveDateTime {
+        let datetime = DateTime::<Utc>::from_timestamp(timestamp, 0)
+            .expect("Invalid date")
+            .naive_utc();
+        if self.is_intraday() {
+            datetime
+        } else {
+            datetime.date().and_time(NaiveTime::MIN)
+        }
+    }
+}
+
+/// Time span ending at the end date
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Range {
+    #[value(name = "1mo")]
+    OneMonth,
+    #[value(name = "6mo")]
+    SixMonths,
+    #[value(name = "1y")]
+    OneYear,
+    #[value(name = "ytd")]
+    YearToDate,
+    #[value(name = "max")]
+    Max,
+}
+
+impl Range {
+    /// Returns the start of the range ending at the given date
+    pub fn start(&self, end_date: OffsetDateTime) -> OffsetDateTime {
+        match self {
+            Range::OneMonth => end_date - Duration::days(30),
+            Range::SixMonths => end_date - Duration::days(30 * 6),
+            Range::OneYear => end_date - Duration::days(365),
+            Range::YearToDate => end_date
+                .replace_ordinal(1)
+                .unwrap()
+                .replace_time(time::Time::MIDNIGHT),
+            Range::Max => OffsetDateTime::UNIX_EPOCH,
+        }
+    }
+}
+
+/// Converts a date given on the command line to a time at the start or end of that day. Fails
+/// for years beyond 9999, which chrono accepts but time cannot represent.
+pub fn date_to_time(date: NaiveDate, end_of_day: bool) -> Result<OffsetDateTime, String> {
+    let time = if end_of_day {
+        NaiveTime::from_hms_opt(23, 59, 59).unwrap()
+    } else {
+        NaiveTime::MIN
+    };
+    OffsetDateTime::from_unix_timestamp(date.and_time(time).and_utc().timestamp())
+        .map_err(|_| format!("The date {} is out of range", date))
+}
+
+/// Whether the US stock market is open, ignoring holidays. The market trades from 9:30 to
+/// 16:00 in New York, which observes daylight saving time from the second Sunday of March
+/// to the first Sunday of November.
+pub fn is_market_open(now: OffsetDateTime) -> bool {
+    let utc = DateTime::<Utc>::from_timestamp(now.unix_timestamp(), 0)
+        .unwrap()
+        .naive_utc();
+    let sunday =
+        |month, n| NaiveDate::from_weekday_of_month_opt(utc.year(), month, Weekday::Sun, n);
+    let daylight_saving = match (sunday(3, 2), sunday(11, 1)) {
+        // The clocks change at 2:00 local time, 7:00 UTC in March and 6:00 UTC in November
+        (Some(start), Some(end)) => {
+            utc >= start.and_hms_opt(7, 0, 0).unwrap() && utc < end.and_hms_opt(6, 0, 0).unwrap()
+        }
+        _ => false,
+    };
+    let new_york = utc - chrono::Duration::hours(if daylight_saving { 4 } else { 5 });
+    let minutes = new_york.hour() * 60 + new_york.minute();
+    new_york.weekday().number_from_monday() <= 5 && (9 * 60 + 30..16 * 60).contains(&minutes)
+}
+
+/// The dates requested on the command line or in a query
+#[derive(Clone, Copy, Debug)]
+pub struct Window {
+    pub range: Range,
+    pub start: Option<NaiveDate>,
+    pub end: Option<NaiveDate>,
+}
+
+impl Window {
+    /// Resolves the requested dates to the start and end of the window to fetch.
+    /// An explicit start date takes precedence over the range.
+    pub fn resolve(
+        &self,
+        today: OffsetDateTime,
+    ) -> Result<(OffsetDateTime, OffsetDateTime), String> {
+        let end_date = match self.end {
+            Some(date) => date_to_time(date, true)?,
+            None => today,
+        };
+        let start_date = match self.start {
+            Some(date) => date_to_time(date, false)?,
+            None => self.range.start(end_date),
+        };
+        Ok((start_date, end_date))
+    }
+
+    /// Converts the resolved window to the chart's time axis. The max range starts at the
+    /// first quote rather than at 1970, and the axis spans at least one bar since the chart
+    /// cannot be drawn over a single point in time.
+    pub fn axis(
+        &self,
+        interval: Interval,
+        (start_date, end_date): (OffsetDateTime, OffsetDateTime),
+        first_quote: NaiveDateTime,
+    ) -> (NaiveDateTime, NaiveDateTime) {
+        let min_date = match self.range {
+            Range::Max if self.start.is_none() => first_quote,
+            _ => interval.bar_time(start_date.unix_timestamp()),
+        };
+        let max_date = interval.bar_time(end_date.unix_timestamp());
+        (min_date, max_date.max(min_date + interval.duration()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn axis_of_a_single_day_spans_one_bar() {
+        let day = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let window = Window {
+            range: Range::SixMonths,
+            start: Some(day),
+            end: Some(day),
+        };
+        let dates = window.resolve(OffsetDateTime::now_utc()).unwrap();
+        let (min_date, max_date) =
+            window.axis(Interval::OneDay, dates, day.and_time(NaiveTime::MIN));
+        assert_eq!(min_date, day.and_time(NaiveTime::MIN));
+        assert_eq!(max_date, min_date + chrono::Duration::days(1));
+    }
+
+    #[test]
+    fn axis_of_the_max_range_with_one_bar_spans_one_bar() {
+        let window = Window {
+            range: Range::Max,
+            start: None,
+            end: None,
+        };
+        let today = date_to_time(NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(), false).unwrap();
+        let first_quote = NaiveDate::from_ymd_opt(2024, 3, 1)
+            .unwrap()
+            .and_time(NaiveTime::MIN);
+        let (min_date, max_date) = window.axis(
+            Interval::OneWeek,
+            window.resolve(today).unwrap(),
+            first_quote,
+        );
+        assert_eq!(min_date, first_quote);
+        assert_eq!(max_date, first_quote + chrono::Duration::weeks(1));
+    }
+
+    #[test]
+    fn dates_beyond_year_9999_are_out_of_range() {
+        let date = NaiveDate::from_ymd_opt(99_999, 1, 1).unwrap();
+        assert_eq!(
+            date_to_time(date, false),
+            Err("The date +99999-01-01 is out of range".to_owned())
+        );
+        let window = Window {
+            range: Range::SixMonths,
+            start: Some(date),
+            end: None,
+        };
+        assert!(window.resolve(OffsetDateTime::now_utc()).is_err());
+    }
+}
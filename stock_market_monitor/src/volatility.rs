@@ -0,0 +1,110 @@
+use clap::ValueEnum;
+use yahoo_finance_api::Quote;
+
+use crate::indicators;
+
+/// How a bar is judged to be volatile
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum VolatilityMeasure {
+    /// The bar's high to low range as a fraction of its close, |(high - low) / close|
+    Range,
+    /// The close to close return magnitude, |close / previous close - 1|
+    Return,
+    /// The bar's true range as a multiple of the average true range of the previous bars
+    Atr,
+    /// The z-score of the return against the rolling standard deviation of the previous returns
+    Zscore,
+}
+
+impl VolatilityMeasure {
+    /// Threshold used when none is given on the command line
+    pub fn default_threshold(&self) -> f64 {
+        match self {
+            VolatilityMeasure::Range | VolatilityMeasure::Return => 0.02,
+            VolatilityMeasure::Atr => 1.5,
+            VolatilityMeasure::Zscore => 2.0,
+        }
+    }
+}
+
+/// The high to low range of a bar as a fraction of its close
+pub fn daily_range(quote: &Quote) -> f64 {
+    ((quote.high - quote.low) / quote.close).abs()
+}
+
+/// Selects the volatile bars of a stock
+#[derive(Clone, Copy, Debug)]
+pub struct Volatility {
+    pub measure: VolatilityMeasure,
+    pub threshold: f64,
+    /// Number of previous bars used by the atr and zscore measures
+    pub window: usize,
+}
+
+impl Volatility {
+    pub fn new(measure: VolatilityMeasure, threshold: Option<f64>, window: usize) -> Self {
+        Volatility {
+            measure,
+            threshold: threshold.unwrap_or_else(|| measure.default_threshold()),
+            window,
+        }
+    }
+
+    /// Flags every quote as volatile or not. Quotes without enough history for
+    /// the measure are never volatile.
+    pub fn flags(&self, quotes: &[Quote]) -> Vec<bool> {
+        let returns: Vec<Option<f64>> = (0..quotes.len())
+            .map(|i| match i {
+                0 => None,
+                _ => Some(quotes[i].close / quotes[i - 1].close - 1.0),
+            })
+            .collect();
+
+        match self.measure {
+            VolatilityMeasure::Range => quotes
+                .iter()
+                .map(|quote| daily_range(quote) > self.threshold)
+                .collect(),
+            VolatilityMeasure::Return => returns
+                .iter()
+                .map(|r| r.is_some_and(|r| r.abs() > self.threshold))
+                .collect(),
+            VolatilityMeasure::Atr => {
+                let atr = indicators::atr(quotes, self.window);
+                (0..quotes.len())
+                    .map(|i| {
+                        let previous_atr = match i {
+                            0 => None,
+                            _ => atr[i - 1],
+                        };
+                        let quote = &quotes[i];
+                        let previous_close = quotes[i.saturating_sub(1)].close;
+                        let true_range = (quote.high - quote.low)
+                            .max((quote.high - previous_close).abs())
+                            .max((quote.low - previous_close).abs());
+                        previous_atr.is_some_and(|atr| true_range > self.threshold * atr)
+                    })
+                    .collect()
+            }
+            VolatilityMeasure::Zscore => (0..quotes.len())
+                .map(|i| {
+                    // Needs a full window of returns before the current one
+                    if self.window < 2 || i < self.window + 1 {
+                        return false;
+                    }
+                    let window: Vec<f64> = returns[i - self.window..i]
+                        .iter()
+                        .flatten()
+                        .copied()
+                        .collect();
+                    let mean = window.iter().sum::<f64>() / window.len() as f64;
+                    let deviation = (window.iter().map(|r| (r - mean).powi(2)).sum::<f64>()
+                        / (window.len() - 1) as f64)
+                        .sqrt();
+                    let current = returns[i].unwrap_or(mean);
+                    deviation > 0.0 && ((current - mean) / deviation).abs() > self.threshold
+                })
+                .collect(),
+        }
+    }
+}